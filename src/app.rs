@@ -3,50 +3,336 @@
 // Licensed under the MPL-2.0 license
 //
 
-use std::path::Path;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::f32::consts::TAU;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
-use async_channel::Receiver;
+use ::image::AnimationDecoder;
+use ::image::RgbaImage;
+use ::image::codecs::gif::GifDecoder;
+use async_channel::{Receiver, Sender};
 use cpal::SupportedStreamConfig;
 use cpal::traits::DeviceTrait;
+use log::{debug, warn};
+use rand::Rng;
+use serde::Deserialize;
 
 use iced::Alignment;
 use iced::Event;
+use iced::Theme;
 use iced::alignment;
+use iced::application::{Appearance, DefaultStyle};
 use iced::event;
-use iced::keyboard::Key;
-use iced::keyboard::key::Named;
 use iced::widget::Container;
+use iced::widget::Row;
 use iced::widget::Space;
 use iced::widget::image::Handle;
 use iced::widget::toggler;
-use iced::widget::{button, column, combo_box, container, image, row, slider, text};
-use iced::{Element, Length};
+use iced::widget::{
+    button, column, combo_box, container, image, mouse_area, pick_list, progress_bar, responsive,
+    row, scrollable, slider, stack, text,
+};
+use iced::window;
+use iced::{Element, Length, Subscription, Task};
 
 use crate::capture;
-use crate::capture::InputDevice;
-use crate::config::ChibiConfig;
+use crate::capture::dsp::amplitude_to_db;
+use crate::capture::{CaptureSession, InputDevice, MicEvent};
+use crate::config::{
+    ChannelMode, ChibiConfig, DeadbandMode, DetectionMode, RendererBackend, StreamFormat,
+};
+use crate::keybinds::KeyAction;
 use crate::lock_and_unlock;
+use crate::metrics::MetricsServer;
+use crate::monitors::{self, MonitorInfo};
+#[cfg(feature = "ndi")]
+use crate::ndi::NdiOutput;
+#[cfg(feature = "tray")]
+use crate::tray::{Tray, TrayAction};
+use crate::websocket::WebSocketServer;
 
 const APP_VERSION: f32 = 1.1;
 
+const PROJECT_URL: &str = "https://github.com/megabytesofrem/chibi";
+const ASSETS_AUTHOR_URL: &str = "https://chereverie.tumblr.com";
+const LICENSE_URL: &str = "https://mozilla.org/MPL/2.0/";
+
+// How long to sample ambient noise for, and how far above the noise to put the threshold
+const CALIBRATION_DURATION: Duration = Duration::from_secs(3);
+const CALIBRATION_MARGIN: f32 = 1.5;
+const CALIBRATION_RANGE: std::ops::RangeInclusive<f32> = 0.01..=0.5;
+
+// Range of the threshold slider and level meter in dB
+const THRESHOLD_DB_RANGE: std::ops::RangeInclusive<f32> = -60.0..=0.0;
+
+// How long the window has to stay put after a resize or move before its geometry is saved
+const GEOMETRY_SAVE_DELAY: Duration = Duration::from_millis(500);
+
+// Number of level readings the debug overlay graphs, and the height of the graph
+const LEVEL_HISTORY_LEN: usize = 120;
+const LEVEL_GRAPH_HEIGHT: f32 = 80.0;
+
+// File extensions of the still image formats avatars can use
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg", "webp"];
+
+// File extensions of avatar bundles, zip archives holding the images and a manifest
+const BUNDLE_EXTENSIONS: &[&str] = &["chibi", "zip"];
+
+// Name of the manifest describing an avatar
+const MANIFEST_FILE: &str = "avatar.toml";
+
+// Range above the threshold over which the bounce grows from nothing to full intensity
+const BOUNCE_RANGE_DB: f32 = 24.0;
+
+// How long a notification is shown before it disappears by itself
+const NOTIFICATION_DURATION: Duration = Duration::from_secs(8);
+
+// How long the eyes stay closed during a blink
+const BLINK_DURATION: Duration = Duration::from_millis(150);
+
+// How long the clipping indicator stays lit after the input clipped
+const CLIP_HOLD: Duration = Duration::from_secs(1);
+
+/// How avatar images are prepared when they are loaded
+#[derive(Debug, Clone, Copy)]
+pub struct ImageOptions {
+    /// Flip the images horizontally
+    pub mirror: bool,
+
+    /// Alpha (0 to 1) at which partly transparent pixels are made opaque rather than
+    /// transparent, if set
+    pub matte_threshold: Option<f32>,
+}
+
+impl From<&ChibiConfig> for ImageOptions {
+    fn from(config: &ChibiConfig) -> Self {
+        Self {
+            mirror: config.mirror_horizontal,
+            matte_threshold: config.chroma_matte.then_some(config.chroma_matte_threshold),
+        }
+    }
+}
+
+/// What the avatar is doing, each of which can have its own image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AvatarState {
+    Idle,
+    Talking,
+    TalkingLoud,
+    Blink,
+    TalkingBlink,
+}
+
+impl AvatarState {
+    const ALL: [AvatarState; 5] = [
+        AvatarState::Idle,
+        AvatarState::Talking,
+        AvatarState::TalkingLoud,
+        AvatarState::Blink,
+        AvatarState::TalkingBlink,
+    ];
+
+    /// Name of the state in the `state_dirs` section of the config
+    pub fn name(self) -> &'static str {
+        match self {
+            AvatarState::Idle => "idle",
+            AvatarState::Talking => "talking",
+            AvatarState::TalkingLoud => "talking_loud",
+            AvatarState::Blink => "blink",
+            AvatarState::TalkingBlink => "talking_blink",
+        }
+    }
+
+    /// The state with the given name, also accepting the short "talk" names
+    fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+
+        let state = match name.as_str() {
+            "talk" => AvatarState::Talking,
+            "talk_loud" => AvatarState::TalkingLoud,
+            "talk_blink" => AvatarState::TalkingBlink,
+            _ => *Self::ALL.iter().find(|state| state.name() == name)?,
+        };
+
+        Some(state)
+    }
+
+    /// State an image in the assets folder is for, going by its file name (e.g.
+    /// `talk.png`), if the name is one of the recognised ones
+    fn from_file_name(path: &Path) -> Option<Self> {
+        Self::from_name(path.file_stem()?.to_str()?)
+    }
+}
+
+/// Description of an avatar, kept as `avatar.toml` along with its images in the assets
+/// folder or bundle
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct AvatarManifest {
+    /// Image for each state, e.g. `idle = "neutral.png"`, or a list of images to play
+    /// back as an animation
+    states: HashMap<String, ManifestFrames>,
+
+    /// How long each frame of an animated state is shown in milliseconds, instead of
+    /// `frame_duration_ms` from the config
+    frame_duration_ms: Option<u32>,
+
+    /// Size of the avatar relative to the window, used when the avatar is chosen
+    scale: Option<f32>,
+}
+
+/// Images the manifest gives a state, either a single one or the frames of an animation
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum ManifestFrames {
+    Single(String),
+    Animation(Vec<String>),
+}
+
+impl ManifestFrames {
+    fn files(&self) -> &[String] {
+        match self {
+            ManifestFrames::Single(file) => std::slice::from_ref(file),
+            ManifestFrames::Animation(files) => files,
+        }
+    }
+}
+
+impl AvatarManifest {
+    /// Parse a manifest, falling back to none at all (and the file name conventions) if
+    /// it is invalid
+    fn parse(contents: &str, source: &Path) -> Self {
+        toml::from_str(contents).unwrap_or_else(|err| {
+            warn!(
+                "Ignoring invalid {} in {}: {}",
+                MANIFEST_FILE,
+                source.display(),
+                err
+            );
+            Self::default()
+        })
+    }
+}
+
+/// One of the numbered avatar images, for picking it in the settings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageChoice(pub usize);
+
+impl fmt::Display for ImageChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Image {}", self.0 + 1)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub enum View {
     Home,
     Settings,
     About,
+    Setup(SetupStep),
+}
+
+/// Steps of the setup shown the first time chibi is run
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SetupStep {
+    Assets,
+    Microphone,
+    Calibrate,
 }
 
 #[derive(Debug, Clone)]
 pub enum Message {
-    MicActive(bool),
+    MicActive(MicEvent),
     ThresholdChanged(f32),
     DeadbandChanged(f32),
+    DetectionModeChanged(DetectionMode),
+    DeadbandModeChanged(DeadbandMode),
+    OffThresholdChanged(f32),
+    HoldTimeChanged(u32),
+    MinOpenChanged(u32),
+    GainChanged(f32),
+    VoiceFilterChanged(bool),
+    NoiseSuppressionChanged(bool),
     InputChanged(InputDevice),
+    RefreshInputDevices,
+    AudioHostChanged(String),
+    MonitorChanged(bool),
+    RecordChanged(bool),
+    MonitorDeviceChanged(String),
+    ChannelModeChanged(ChannelMode),
+    StreamFormatChanged(Option<StreamFormat>),
+    ThemeChanged(Theme),
+    RendererChanged(RendererBackend),
     FlickerChanged(bool),
+    FlickerMinChanged(u32),
+    FlickerMaxChanged(u32),
+    ConfirmQuitChanged(bool),
+    BreathingChanged(bool),
+    BlinkChanged(bool),
+    AvatarScaleChanged(f32),
+    MirrorChanged(bool),
+    WindowMonitorChanged(MonitorInfo),
+    ChromaMatteChanged(bool),
+    BounceIntensityChanged(f32),
+    AvatarPressed,
+    AvatarMoved(iced::Point),
+    AvatarReleased,
+    ChromaColorChanged([f32; 3]),
+    TransparentBackgroundChanged(bool),
+    AlwaysOnTopChanged(bool),
+    PickAssetsFolder,
+    PickAssetsBundle,
+    OpenUrl(&'static str),
+    AssetsPathChanged(Option<PathBuf>),
+    ToggleMicTest,
+    CalibrateNoiseFloor,
+    FinishSetup,
+    ToggleMute,
+    ToggleFreeze,
+    ToggleDebugOverlay,
+    FreezeImageChanged(ImageChoice),
+    ClosedImageChanged(ImageChoice),
+    OpenImageChanged(ImageChoice),
+    ToggleMiniMode,
+    ToggleChromaKey,
+    #[cfg(feature = "tray")]
+    PollTray,
+    #[cfg(feature = "ndi")]
+    CaptureNdiFrame,
+    #[cfg(feature = "ndi")]
+    NdiFrameCaptured(window::Screenshot),
+    #[cfg(feature = "tray")]
+    MinimizeToTrayChanged(bool),
+    #[cfg(feature = "tray")]
+    ShowWindow,
+    ResetConfig,
     SwitchView(View),
+    ProfileChanged(String),
     AppEvent(iced::Event),
+    CloseRequested,
+    ConfirmQuit,
+    CancelQuit,
+    AnimationTick(Instant),
+    BlinkTick(Instant),
+    FrameTick(Instant),
+    CrossfadeTick(Instant),
+    CrossfadeChanged(u32),
+    ShowNotification(String),
+    DismissNotification,
+    NotificationTick(Instant),
+    GeometrySaveTick(Instant),
+    CalibrationTick(Instant),
+}
+
+// Ambient noise calibration in progress
+struct Calibration {
+    started: Instant,
+    readings: Vec<f32>,
 }
 
 // Internal application state
@@ -55,22 +341,114 @@ pub struct ChibiApp {
     pub config: Arc<Mutex<ChibiConfig>>,
 
     images: Arc<Vec<Handle>>,
+    named_images: Arc<HashMap<AvatarState, Handle>>,
+    state_frames: Arc<HashMap<String, Vec<Handle>>>,
 
     // Input device state
     pub available_input_devices: combo_box::State<InputDevice>,
     pub selected_input_device: Option<InputDevice>,
-    pub selected_input_config: SupportedStreamConfig,
+    pub selected_input_config: Option<SupportedStreamConfig>,
+    stream_formats: Vec<StreamFormat>,
+    host_names: Vec<String>,
+
+    // Output devices of the selected host, for monitoring the input
+    output_devices: Vec<String>,
+
+    // Monitors the window can be moved to
+    monitors: Vec<MonitorInfo>,
+    pub capture_session: Option<CaptureSession>,
+
+    // Shown when the saved input device could not be found
+    device_notice: Option<String>,
+
+    // Whether the window was created with a transparent background
+    pub transparent_window: bool,
+
+    // Drive detection from a generated signal instead of the input device
+    pub test_signal: bool,
 
     // UI events
     mic_activated: bool,
-    show_buttons: bool,
-    show_modal: bool,
-    chroma_key: bool,
+    muted: bool,
+    mic_testing: bool,
 
-    // Currently displayed image
-    curr_view: View,
+    // Image held regardless of the microphone while the avatar is frozen
+    frozen_image: Option<usize>,
+    fullscreen: bool,
+
+    // Last cursor position over the avatar area, and where a drag started along with the
+    // avatar offset at that point
+    cursor_position: iced::Point,
+    drag_start: Option<(iced::Point, [f32; 2])>,
+    quit_requested: bool,
+
+    // When the window was last resized or moved, while that change is yet to be saved
+    geometry_changed: Option<Instant>,
+
+    // Currently displayed view and image
+    pub curr_view: View,
     curr_image: Option<Handle>,
-    pub receiver: Option<Receiver<bool>>,
+
+    // Image being faded out after the talking state changed, and when the fade started
+    fading_image: Option<(Handle, Instant)>,
+    talking_tier: Option<usize>,
+    splash_image: Option<Handle>,
+    pub receiver: Option<Receiver<MicEvent>>,
+
+    // Sender handed to the capture thread, kept so capture can be restarted
+    pub sender: Option<Sender<MicEvent>>,
+
+    // Sends messages to show in the notification banner, e.g. from the capture thread
+    pub notifier: Option<Sender<String>>,
+    notification: Option<(String, Instant)>,
+
+    // Broadcasts detection events to overlays, if enabled
+    pub websocket: Option<WebSocketServer>,
+    pub metrics: Option<MetricsServer>,
+
+    #[cfg(feature = "ndi")]
+    pub ndi: Option<NdiOutput>,
+
+    #[cfg(feature = "tray")]
+    pub tray: Option<Tray>,
+
+    // Time spent talking this session, and when the current stretch of talking started
+    speaking_time: Duration,
+    talking_since: Option<Instant>,
+
+    // Most recent levels reported by the detector
+    raw_level: f32,
+    processed_level: f32,
+    clipped_at: Option<Instant>,
+
+    // Recent processed levels in dB and whether the microphone was active at each, graphed
+    // by the debug overlay while it is shown
+    debug_overlay: bool,
+    level_history: VecDeque<(f32, bool)>,
+
+    calibration: Option<Calibration>,
+    calibration_result: Option<String>,
+
+    // Idle breathing animation
+    animation_start: Instant,
+    breathing_weight: f32,
+    breathing_scale: f32,
+
+    // Blinking, independent of the microphone
+    next_blink: Instant,
+    blinking_until: Option<Instant>,
+
+    // Current frame of animated avatar states
+    frame_index: usize,
+
+    // Images shown while idle and talking, copied from the config so they can be looked up
+    // while it is locked
+    closed_image: usize,
+    open_image: usize,
+
+    // Frame duration and scale given by the avatar's manifest, if it has one
+    avatar_frame_duration_ms: Option<u32>,
+    avatar_scale: Option<f32>,
 }
 
 // App implementation
@@ -85,6 +463,14 @@ fn aligned_button<'a>(text: &'a str) -> button::Button<'a, Message> {
     .padding(5)
 }
 
+/// Text button styled as a link, opening `url` in the browser
+fn link<'a>(label: &'a str, url: &'static str) -> button::Button<'a, Message> {
+    button(text(label).size(12))
+        .padding(0)
+        .style(button::text)
+        .on_press(Message::OpenUrl(url))
+}
+
 fn detailed_slider<'a, Message>(
     label: String,
     detail: String,
@@ -101,112 +487,500 @@ where
             .size(12)
             .width(Length::Fill)
             .color([0.8, 0.8, 0.8]),
-        container(slider(range, value, move |v| message(v)).step(0.01),),
+        container(slider(range, value, message).step(0.01),),
     ])
 }
 
+/// Pick list of the numbered avatar images, with a thumbnail of the chosen one
+fn image_picker<'a>(
+    label: &'a str,
+    images: &[Handle],
+    selected: usize,
+    on_select: fn(ImageChoice) -> Message,
+) -> Row<'a, Message> {
+    let thumbnail: Element<'a, Message> = match images.get(selected) {
+        Some(handle) => image(handle.clone())
+            .width(Length::Fixed(48.0))
+            .height(Length::Fixed(48.0))
+            .into(),
+        None => Space::new(Length::Fixed(48.0), Length::Fixed(48.0)).into(),
+    };
+
+    row![
+        text(label).size(14).width(Length::Fill),
+        pick_list(
+            (0..images.len()).map(ImageChoice).collect::<Vec<_>>(),
+            Some(ImageChoice(selected)),
+            on_select
+        ),
+        thumbnail,
+    ]
+    .spacing(10)
+    .align_y(Alignment::Center)
+}
+
+fn level_meter<'a>(label: &'a str, level: f32) -> Container<'a, Message> {
+    container(column![
+        text(format!("{}: {:.2}", label, level)).size(12),
+        progress_bar(0.0..=1.0, level).height(Length::Fixed(8.0)),
+    ])
+    .width(Length::Fill)
+}
+
+/// Window level for the always on top setting
+pub fn window_level(always_on_top: bool) -> window::Level {
+    if always_on_top {
+        window::Level::AlwaysOnTop
+    } else {
+        window::Level::Normal
+    }
+}
+
+/// Format a duration as e.g. "1h 02m 03s", leaving out hours when there are none
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+
+    if hours > 0 {
+        format!("{}h {:02}m {:02}s", hours, minutes, seconds)
+    } else {
+        format!("{}m {:02}s", minutes, seconds)
+    }
+}
+
+/// Look up a built-in theme by its display name, falling back to Tokyo Night
+pub fn theme_by_name(name: &str) -> Theme {
+    Theme::ALL
+        .iter()
+        .find(|theme| theme.to_string() == name)
+        .cloned()
+        .unwrap_or(Theme::TokyoNight)
+}
+
+/// Slider for a single component of an RGB color
+fn color_slider<'a>(label: &'a str, color: [f32; 3], component: usize) -> Row<'a, Message> {
+    row![
+        text(label).size(12).width(Length::Fixed(15.0)),
+        slider(0.0..=1.0, color[component], move |value| {
+            let mut color = color;
+            color[component] = (value * 100.0).round() / 100.0;
+            Message::ChromaColorChanged(color)
+        })
+        .step(0.01),
+    ]
+    .spacing(5)
+}
+
+/// Split a bar into two proportional parts at `fraction` (clamped to 0..=1)
+fn proportional_lengths(fraction: f32) -> (Length, Length) {
+    let filled = (fraction.clamp(0.0, 1.0) * 1000.0).round() as u16;
+    (
+        Length::FillPortion(filled),
+        Length::FillPortion(1000 - filled),
+    )
+}
+
+/// Position of a level in dB along the threshold slider, from 0 to 1
+fn db_fraction(db: f32) -> f32 {
+    let (min, max) = (*THRESHOLD_DB_RANGE.start(), *THRESHOLD_DB_RANGE.end());
+    (db - min) / (max - min)
+}
+
+/// Horizontal bar showing the current level, with a marker at the threshold (both in dB)
+fn threshold_meter<'a>(level_db: f32, threshold_db: f32) -> Container<'a, Message> {
+    let (level_width, level_rest) = proportional_lengths(db_fraction(level_db));
+    let (marker_offset, marker_rest) = proportional_lengths(db_fraction(threshold_db));
+
+    let bar_style = |color: iced::Color| {
+        move |_: &iced::Theme| container::Style {
+            background: Some(iced::Background::Color(color)),
+            ..Default::default()
+        }
+    };
+
+    let background = container(Space::new(Length::Fill, Length::Fill))
+        .style(bar_style(iced::Color::from_rgb(0.2, 0.2, 0.25)));
+
+    let level_bar = row![
+        container(Space::new(Length::Fill, Length::Fill))
+            .width(level_width)
+            .style(bar_style(iced::Color::from_rgb(0.4, 0.8, 0.4))),
+        Space::new(level_rest, Length::Fill),
+    ];
+
+    let marker = row![
+        Space::new(marker_offset, Length::Fill),
+        container(Space::new(Length::Fixed(2.0), Length::Fill))
+            .style(bar_style(iced::Color::from_rgb(1.0, 0.3, 0.3))),
+        Space::new(marker_rest, Length::Fill),
+    ];
+
+    container(stack![background, level_bar, marker])
+        .width(Length::Fill)
+        .height(Length::Fixed(12.0))
+}
+
 impl Default for ChibiApp {
     fn default() -> Self {
         Self {
             config: Arc::new(Mutex::new(ChibiConfig::default())),
             images: Arc::new(vec![]),
-            available_input_devices: combo_box::State::new(capture::get_input_devices()),
-            selected_input_device: capture::get_default_device(),
-            selected_input_config: capture::get_default_device()
-                .unwrap()
-                .raw_device
-                .default_input_config()
-                .unwrap(),
+            named_images: Arc::new(HashMap::new()),
+            state_frames: Arc::new(HashMap::new()),
+            available_input_devices: combo_box::State::new(vec![]),
+            selected_input_device: None,
+            selected_input_config: None,
+            stream_formats: vec![],
+            host_names: capture::get_host_names(),
+            output_devices: vec![],
+            monitors: monitors::get_monitors(),
+            capture_session: None,
+            device_notice: None,
+            transparent_window: false,
+            test_signal: false,
             mic_activated: false,
-            show_buttons: true,
-            show_modal: false,
-            chroma_key: false,
+            muted: false,
+            frozen_image: None,
+            mic_testing: false,
+            fullscreen: false,
+            cursor_position: iced::Point::ORIGIN,
+            drag_start: None,
+            quit_requested: false,
+            geometry_changed: None,
             curr_view: View::Home,
             curr_image: None,
+            fading_image: None,
+            talking_tier: None,
+            splash_image: None,
             receiver: None,
+            sender: None,
+            notifier: None,
+            notification: None,
+            websocket: None,
+            metrics: None,
+            #[cfg(feature = "ndi")]
+            ndi: None,
+            #[cfg(feature = "tray")]
+            tray: None,
+            speaking_time: Duration::ZERO,
+            talking_since: None,
+            raw_level: 0.0,
+            processed_level: 0.0,
+            clipped_at: None,
+            debug_overlay: false,
+            level_history: VecDeque::new(),
+            calibration: None,
+            calibration_result: None,
+            animation_start: Instant::now(),
+            breathing_weight: 1.0,
+            breathing_scale: 1.0,
+            next_blink: Instant::now(),
+            blinking_until: None,
+            frame_index: 0,
+            closed_image: 0,
+            open_image: 1,
+            avatar_frame_duration_ms: None,
+            avatar_scale: None,
         }
     }
 }
 
 impl ChibiApp {
-    fn view_home<'a>(&self) -> Element<Message> {
+    fn view_home(&self) -> Element<'_, Message> {
+        // Until the first detection event arrives, show the splash image (or the idle image)
         let avatar_image = self
             .curr_image
             .clone()
-            .unwrap_or(self.get_image(0).unwrap().clone());
+            .or_else(|| self.splash_image.clone())
+            .or_else(|| {
+                self.state_image(AvatarState::Idle, self.closed_image)
+                    .cloned()
+            });
 
-        let buttons = if self.show_buttons {
-            row![
-                aligned_button("Settings").on_press(Message::SwitchView(View::Settings)),
-                aligned_button("About").on_press(Message::SwitchView(View::About)),
-            ]
-            .spacing(5)
-        } else {
-            row![Space::new(Length::Fill, Length::Fill)]
-        };
+        let config = lock_and_unlock!(self.config);
+        let avatar_scale = config.avatar_scale * self.breathing_scale * self.bounce_scale(&config);
+        let [offset_x, offset_y] = config.avatar_offset;
 
-        let layout = column![
-            column![
-                image(avatar_image)
-                    .width(Length::Fixed(300.0))
-                    .height(Length::Fixed(300.0)),
-                if self.show_buttons {
-                    text(format!("Microphone activated: {}", self.mic_activated)).size(12)
-                } else {
-                    text("")
-                }
-            ]
-            .align_x(Alignment::Center)
-            .width(Length::Fill),
-            Space::new(Length::Fill, Length::Fill),
-            buttons
-        ];
+        // Fade from the previous image to the current one, if a fade is running
+        let fade = self.fading_image.clone().map(|(image, started)| {
+            let duration = config.crossfade_ms.max(1) as f32;
+            let progress = started.elapsed().as_millis() as f32 / duration;
+            (image, progress.clamp(0.0, 1.0))
+        });
+
+        // Size the avatar relative to the space left over in the window
+        let avatar = responsive(move |size| {
+            let avatar_size = size.width.min(size.height) * avatar_scale;
+
+            let sized = |handle: Handle, opacity: f32| {
+                image(handle)
+                    .width(Length::Fixed(avatar_size))
+                    .height(Length::Fixed(avatar_size))
+                    .opacity(opacity)
+            };
+
+            let avatar: Element<'_, Message> = match (avatar_image.clone(), fade.clone()) {
+                (Some(avatar_image), Some((fading_image, progress))) => mouse_area(stack![
+                    sized(fading_image, 1.0 - progress),
+                    sized(avatar_image, progress),
+                ])
+                .on_press(Message::AvatarPressed)
+                .into(),
+                (Some(avatar_image), None) => mouse_area(sized(avatar_image, 1.0))
+                    .on_press(Message::AvatarPressed)
+                    .into(),
+                (None, _) => column![
+                    text("No avatar loaded").size(14),
+                    text("Choose a folder containing your avatar images")
+                        .color([0.8, 0.8, 0.8])
+                        .size(12),
+                    aligned_button("Choose folder").on_press(Message::PickAssetsFolder),
+                ]
+                .align_x(Alignment::Center)
+                .spacing(5)
+                .into(),
+            };
+
+            // Padding one side by twice the offset moves the center by the offset
+            let padding = iced::Padding {
+                top: (offset_y * 2.0).max(0.0),
+                bottom: (-offset_y * 2.0).max(0.0),
+                left: (offset_x * 2.0).max(0.0),
+                right: (-offset_x * 2.0).max(0.0),
+            };
+
+            container(avatar)
+                .center(Length::Fill)
+                .padding(padding)
+                .into()
+        });
+
+        // Movement is tracked over the whole area, so the avatar can be dragged anywhere in it
+        let avatar = mouse_area(avatar)
+            .on_move(Message::AvatarMoved)
+            .on_release(Message::AvatarReleased)
+            .on_exit(Message::AvatarReleased);
+
+        let mut layout = column![avatar].align_x(Alignment::Center).spacing(5);
+
+        // Fullscreen and mini mode are presentation modes, so only the avatar is shown
+        let presenting = self.fullscreen || config.mini_mode;
+        let padding = if config.mini_mode { 0 } else { 15 };
+
+        if config.show_buttons && !presenting {
+            layout = layout.push(text(self.mic_status()).size(12));
+
+            if let Some(notice) = &self.device_notice {
+                layout = layout.push(text(notice).size(12).color([1.0, 0.6, 0.4]));
+            }
+            layout = layout.push(
+                row![
+                    aligned_button("Settings").on_press(Message::SwitchView(View::Settings)),
+                    aligned_button("About").on_press(Message::SwitchView(View::About)),
+                ]
+                .spacing(5),
+            );
+        }
+
+        // Shown even with the UI hidden, since that is when detection matters
+        if self.debug_overlay {
+            layout = layout.push(self.view_level_graph(&config));
+        }
+
+        if config.chroma_key {
+            let [r, g, b] = config.chroma_key_color;
 
-        if self.chroma_key {
             container(layout)
                 .width(Length::Fill)
                 .height(Length::Fill)
-                .style(|_| container::Style {
-                    background: Some(iced::Background::Color(iced::Color::from_rgb(
-                        1.0, 0.0, 1.0,
-                    ))),
+                .style(move |_| container::Style {
+                    background: Some(iced::Background::Color(iced::Color::from_rgb(r, g, b))),
                     ..Default::default()
                 })
-                .padding(15)
+                .padding(padding)
                 .into()
         } else {
             container(layout)
                 .width(Length::Fill)
                 .height(Length::Fill)
-                .padding(15)
+                .padding(padding)
                 .into()
         }
     }
 
-    fn view_settings<'a>(&self) -> Element<Message> {
+    /// Scrolling graph of the recent levels against the thresholds, for seeing why
+    /// detection behaves the way it does
+    fn view_level_graph(&self, config: &ChibiConfig) -> Element<'_, Message> {
+        let (min_db, max_db) = (*THRESHOLD_DB_RANGE.start(), *THRESHOLD_DB_RANGE.end());
+        let height_of =
+            |db: f32| (db.clamp(min_db, max_db) - min_db) / (max_db - min_db) * LEVEL_GRAPH_HEIGHT;
+
+        let bar = |height: f32, color: iced::Color| {
+            container(Space::new(Length::Fill, Length::Fill))
+                .width(Length::Fill)
+                .height(Length::Fixed(height))
+                .style(move |_| container::Style {
+                    background: Some(iced::Background::Color(color)),
+                    ..Default::default()
+                })
+        };
+
+        // Oldest readings on the left, padded with silence until the history fills up
+        let padding = LEVEL_HISTORY_LEN - self.level_history.len();
+        let bars = (0..padding)
+            .map(|_| (min_db, false))
+            .chain(self.level_history.iter().copied())
+            .map(|(db, active)| {
+                let color = if active {
+                    iced::Color::from_rgb(0.4, 0.8, 0.4)
+                } else {
+                    iced::Color::from_rgb(0.5, 0.5, 0.5)
+                };
+
+                bar(height_of(db), color).into()
+            });
+        let bars = Row::with_children(bars)
+            .height(Length::Fixed(LEVEL_GRAPH_HEIGHT))
+            .align_y(Alignment::End);
+
+        // Horizontal lines marking where the microphone activates and deactivates
+        let line = |db: f32, color: iced::Color| {
+            column![
+                Space::with_height(Length::Fixed(LEVEL_GRAPH_HEIGHT - height_of(db))),
+                bar(1.0, color),
+            ]
+            .height(Length::Fixed(LEVEL_GRAPH_HEIGHT))
+        };
+        let threshold_db = config.microphone_threshold_db;
+        let threshold_off_db = config.threshold_off_db();
+
+        let latest_db = self.level_history.back().map_or(min_db, |&(db, _)| db);
+
+        container(
+            column![
+                text(format!(
+                    "Level: {:.1} dB, threshold: {:.1} dB, off below: {:.1} dB",
+                    latest_db, threshold_db, threshold_off_db
+                ))
+                .size(12),
+                stack![
+                    bars,
+                    line(threshold_off_db, iced::Color::from_rgb(0.9, 0.7, 0.3)),
+                    line(threshold_db, iced::Color::from_rgb(0.9, 0.3, 0.3)),
+                ],
+            ]
+            .spacing(5),
+        )
+        .width(Length::Fill)
+        .padding(10)
+        .style(container::dark)
+        .into()
+    }
+
+    fn view_settings(&self) -> Element<'_, Message> {
         let config = crate::lock_and_unlock!(self.config);
 
-        // FIXME: Combobox shows up initially as "default" when nothing is selected
         let threshold_slider = detailed_slider(
-            format!("Microphone threshold: {:.2}", config.microphone_threshold).into(),
+            format!(
+                "Microphone threshold: {:.1} dB",
+                config.microphone_threshold_db
+            ),
             "Adjust the microphone detection threshold. \
             Too low of a value may cause the microphone to activate too easily."
                 .trim()
                 .into(),
-            0.0..=1.0,
-            config.microphone_threshold,
-            |value| Message::ThresholdChanged((value * 100.0).round() / 100.0),
+            THRESHOLD_DB_RANGE,
+            config.microphone_threshold_db,
+            |value| Message::ThresholdChanged((value * 10.0).round() / 10.0),
+        );
+
+        let detection_picker = column![
+            row![
+                text("Detection:").size(14).width(Length::Fill),
+                pick_list(
+                    DetectionMode::ALL,
+                    Some(config.detection_mode),
+                    Message::DetectionModeChanged
+                ),
+            ]
+            .align_y(Alignment::Center),
+            text(
+                "RMS is steady, peak reacts faster to sharp consonants. \
+                Peak levels run higher, so raise the threshold or recalibrate after switching."
+            )
+            .color([0.8, 0.8, 0.8])
+            .size(12),
+        ]
+        .spacing(5);
+
+        let deadband_slider = match config.deadband_mode {
+            DeadbandMode::Factor => detailed_slider(
+                format!("Deadband factor: {:.2}", config.deadband_factor),
+                format!(
+                    "Adjust the deadband factor. \
+                    Deadband that determines when the microphone stays active prior to a signal drop off. \
+                    Currently stays active until the level drops below {:.1} dB.",
+                    config.threshold_off_db()
+                ),
+                0.0..=1.0,
+                config.deadband_factor,
+                |value| Message::DeadbandChanged((value * 100.0).round() / 100.0),
+            ),
+            DeadbandMode::Absolute => detailed_slider(
+                format!("Off threshold: {:.1} dB", config.off_threshold_db),
+                "The microphone stays active until the level drops below this. \
+                Values above the microphone threshold act like the threshold."
+                    .into(),
+                THRESHOLD_DB_RANGE,
+                config.off_threshold_db,
+                |value| Message::OffThresholdChanged((value * 10.0).round() / 10.0),
+            ),
+        };
+
+        let deadband_slider = column![
+            row![
+                text("Deadband:").size(14).width(Length::Fill),
+                pick_list(
+                    DeadbandMode::ALL,
+                    Some(config.deadband_mode),
+                    Message::DeadbandModeChanged
+                ),
+            ]
+            .align_y(Alignment::Center),
+            deadband_slider,
+        ]
+        .spacing(5);
+
+        let hold_slider = detailed_slider(
+            format!("Hold time: {} ms", config.hold_time_ms),
+            "Keep the microphone active for at least this long after speaking, \
+            so short pauses between words don't close the mouth."
+                .into(),
+            0.0..=1000.0,
+            config.hold_time_ms as f32,
+            |value| Message::HoldTimeChanged(value.round() as u32),
+        );
+
+        let min_open_slider = detailed_slider(
+            format!("Ignore noises shorter than: {} ms", config.min_open_ms),
+            "Only become active once the microphone has been loud for this long, so a \
+            single loud noise like a door slam or keyboard clack doesn't flash the \
+            mouth open."
+                .into(),
+            0.0..=500.0,
+            config.min_open_ms as f32,
+            |value| Message::MinOpenChanged(value.round() as u32),
         );
 
-        let deadband_slider = detailed_slider(
-            format!("Deadband factor: {:.2}", config.deadband_factor).into(),
-            "Adjust the deadband factor. \
-            Deadband that determines when the microphone stays active prior to a signal drop off"
+        let gain_slider = detailed_slider(
+            format!("Input gain: {:.2}", config.input_gain),
+            "Amplify the microphone signal before it is compared against the threshold. \
+            Useful for quiet microphones."
                 .trim()
                 .into(),
-            0.0..=1.0,
-            config.deadband_factor,
-            |value| Message::DeadbandChanged((value * 100.0).round() / 100.0),
+            1.0..=10.0,
+            config.input_gain,
+            |value| Message::GainChanged((value * 100.0).round() / 100.0),
         );
 
         let flicker_toggler = column![
@@ -218,6 +992,149 @@ impl ChibiApp {
                 .size(12),
         ];
 
+        let flicker_toggler = if config.flicker_input {
+            flicker_toggler
+                .push(detailed_slider(
+                    format!("Shortest flicker: {} ms", config.flicker_min_ms),
+                    "Minimum time before the flicker flips.".into(),
+                    10.0..=500.0,
+                    config.flicker_min_ms as f32,
+                    |value| Message::FlickerMinChanged(value.round() as u32),
+                ))
+                .push(detailed_slider(
+                    format!("Longest flicker: {} ms", config.flicker_max_ms),
+                    "Maximum time before the flicker flips.".into(),
+                    10.0..=500.0,
+                    config.flicker_max_ms as f32,
+                    |value| Message::FlickerMaxChanged(value.round() as u32),
+                ))
+                .spacing(10)
+        } else {
+            flicker_toggler
+        };
+
+        let scale_slider = detailed_slider(
+            format!("Avatar size: {:.0}%", config.avatar_scale * 100.0),
+            "Size of the avatar relative to the window.".into(),
+            0.1..=1.0,
+            config.avatar_scale,
+            |value| Message::AvatarScaleChanged((value * 100.0).round() / 100.0),
+        );
+
+        let bounce_slider = detailed_slider(
+            format!("Bounce with voice: {:.0}%", config.bounce_intensity * 100.0),
+            "Grows the avatar the louder you talk. 0% turns the bounce off.".into(),
+            0.0..=0.5,
+            config.bounce_intensity,
+            |value| Message::BounceIntensityChanged((value * 100.0).round() / 100.0),
+        );
+
+        let crossfade_slider = detailed_slider(
+            format!("Crossfade: {} ms", config.crossfade_ms),
+            "Fade between images when you start or stop talking. 0 ms swaps them instantly.".into(),
+            0.0..=500.0,
+            config.crossfade_ms as f32,
+            |value| Message::CrossfadeChanged(value.round() as u32),
+        );
+
+        let breathing_toggler = column![
+            toggler(config.breathing_enabled)
+                .label("Breathing animation while idle")
+                .on_toggle(Message::BreathingChanged),
+            text("Gently pulses the avatar while the microphone is not active.")
+                .color([0.8, 0.8, 0.8])
+                .size(12),
+        ];
+
+        let color = config.chroma_key_color;
+        let [r, g, b] = color;
+
+        let chroma_color = column![
+            row![
+                text("Chroma key color").size(14).width(Length::Fill),
+                container(Space::new(Length::Fixed(30.0), Length::Fixed(15.0))).style(move |_| {
+                    container::Style {
+                        background: Some(iced::Background::Color(iced::Color::from_rgb(r, g, b))),
+                        ..Default::default()
+                    }
+                }),
+            ],
+            color_slider("R", color, 0),
+            color_slider("G", color, 1),
+            color_slider("B", color, 2),
+            toggler(config.chroma_matte)
+                .label("Sharpen edges for chroma keying")
+                .on_toggle(Message::ChromaMatteChanged),
+            text("Removes the fringe of chroma key color around the avatar after keying, at the cost of smooth edges.")
+                .color([0.8, 0.8, 0.8])
+                .size(12),
+        ]
+        .spacing(5);
+
+        let transparent_toggler = column![
+            toggler(config.transparent_background)
+                .label("Transparent background")
+                .on_toggle(Message::TransparentBackgroundChanged),
+            text(
+                "Lets the desktop show through behind the avatar instead of chroma keying. \
+                Requires a compositor that supports it, and a restart to take effect."
+            )
+            .color([0.8, 0.8, 0.8])
+            .size(12),
+        ];
+
+        let mirror_toggler = toggler(config.mirror_horizontal)
+            .label("Mirror the avatar horizontally")
+            .on_toggle(Message::MirrorChanged);
+
+        let on_top_toggler = toggler(config.always_on_top)
+            .label("Keep the window above other windows")
+            .on_toggle(Message::AlwaysOnTopChanged);
+
+        let selected_monitor = config
+            .window_monitor
+            .and_then(|index| self.monitors.get(index).cloned());
+
+        let monitor_picker = column![
+            text("Monitor:").size(14),
+            pick_list(
+                self.monitors.as_slice(),
+                selected_monitor,
+                Message::WindowMonitorChanged
+            )
+            .placeholder("Wherever the window was last"),
+            text("The window opens on this monitor at startup.")
+                .color([0.8, 0.8, 0.8])
+                .size(12),
+        ]
+        .spacing(5);
+
+        let mini_toggler = toggler(config.mini_mode)
+            .label("Mini mode, showing only the avatar in a small window")
+            .on_toggle(|_| Message::ToggleMiniMode);
+
+        let blink_toggler = column![
+            toggler(config.blink_enabled)
+                .label("Blink at random intervals")
+                .on_toggle(Message::BlinkChanged),
+            text("Requires a blink.png (and optionally talk_blink.png) image, or 'blink' frames in the state folders.")
+                .color([0.8, 0.8, 0.8])
+                .size(12),
+        ];
+
+        let quit_toggler = toggler(config.confirm_quit)
+            .label("Ask for confirmation before quitting")
+            .on_toggle(Message::ConfirmQuitChanged);
+
+        #[cfg(feature = "tray")]
+        let quit_toggler = column![
+            quit_toggler,
+            toggler(config.minimize_to_tray)
+                .label("Minimize to the tray when closing the window")
+                .on_toggle(Message::MinimizeToTrayChanged),
+        ]
+        .spacing(10);
+
         let combo_input = column![
             text("Select an input device:").size(14),
             combo_box(
@@ -226,47 +1143,411 @@ impl ChibiApp {
                 self.selected_input_device.as_ref(),
                 Message::InputChanged,
             ),
-            text("After selecting an input device, you will need to restart the application.")
+            aligned_button("Refresh devices").on_press(Message::RefreshInputDevices),
+        ]
+        .spacing(5);
+
+        let combo_input = match &self.device_notice {
+            Some(notice) => combo_input.push(text(notice).size(12).color([1.0, 0.6, 0.4])),
+            None => combo_input,
+        };
+
+        // What the device actually opened with, useful when levels look wrong
+        let combo_input = match &self.selected_input_config {
+            Some(input_config) => combo_input.push(
+                text(format!(
+                    "{} Hz, {} channel(s), {} samples",
+                    input_config.sample_rate().0,
+                    input_config.channels(),
+                    input_config.sample_format()
+                ))
                 .color([0.8, 0.8, 0.8])
-                .size(12)
-        ];
+                .size(12),
+            ),
+            None => combo_input,
+        };
 
-        let ui_hints = column![
-            text("Press 'ESC' to show/hide UI elements")
+        let host_picker = column![
+            text("Audio host:").size(14),
+            pick_list(
+                self.host_names.as_slice(),
+                config.audio_host.clone(),
+                Message::AudioHostChanged
+            )
+            .placeholder("Default"),
+            text("The audio backend devices are listed from, e.g. ALSA, JACK or WASAPI.")
                 .color([0.8, 0.8, 0.8])
                 .size(12),
-            text("Press 'c' to toggle chroma key")
+        ]
+        .spacing(5);
+
+        let format_picker = column![
+            text("Input format:").size(14),
+            row![
+                pick_list(
+                    self.stream_formats.as_slice(),
+                    config.input_format,
+                    |format| Message::StreamFormatChanged(Some(format))
+                )
+                .placeholder("Device default")
+                .width(Length::Fill),
+                button(text("Use default").size(14))
+                    .padding(5)
+                    .on_press(Message::StreamFormatChanged(None)),
+            ]
+            .spacing(5),
+            text("Only change this if the device's default format doesn't work well.")
                 .color([0.8, 0.8, 0.8])
                 .size(12),
-        ];
+        ]
+        .spacing(5);
 
-        let layout = column![
-            threshold_slider,
-            deadband_slider,
-            flicker_toggler,
-            combo_input,
-            Space::new(Length::Fill, Length::Fill),
-            ui_hints,
-            text(format!("Microphone activated: {}", self.mic_activated)).size(12),
-            Space::new(Length::Fill, Length::Fill),
-            aligned_button("Back").on_press(Message::SwitchView(View::Home))
+        let monitor = column![
+            toggler(config.monitor_enabled)
+                .label("Monitor input")
+                .on_toggle(Message::MonitorChanged),
+            pick_list(
+                self.output_devices.as_slice(),
+                config.monitor_device.clone(),
+                Message::MonitorDeviceChanged
+            )
+            .placeholder("Default output device"),
+            text("Plays back what chibi hears after processing. Use headphones, on speakers this will feed back into the microphone.")
+                .color([0.8, 0.8, 0.8])
+                .size(12),
         ]
-        .spacing(10)
-        .padding(15);
+        .spacing(5);
 
-        container(layout)
-            .width(Length::Fill)
-            .height(Length::Fill)
-            .into()
-    }
+        let record_toggler = column![
+            toggler(config.record_to_wav)
+                .label("Record to WAV")
+                .on_toggle(Message::RecordChanged),
+            text(format!(
+                "Saves what the microphone picked up while active to {}",
+                config.recordings_dir().display()
+            ))
+            .color([0.8, 0.8, 0.8])
+            .size(12),
+        ]
+        .spacing(5);
+
+        let channel_picker = column![
+            text("Input channel:").size(14),
+            pick_list(
+                ChannelMode::ALL,
+                Some(config.channel_mode),
+                Message::ChannelModeChanged
+            ),
+            text("For stereo devices where the microphone is only on one side.")
+                .color([0.8, 0.8, 0.8])
+                .size(12),
+        ]
+        .spacing(5);
+
+        let theme_picker = column![
+            text("Theme:").size(14),
+            pick_list(
+                Theme::ALL,
+                Some(theme_by_name(&config.theme)),
+                Message::ThemeChanged
+            ),
+        ]
+        .spacing(5);
+
+        let renderer_picker = column![
+            text("Renderer:").size(14),
+            pick_list(
+                RendererBackend::ALL,
+                Some(config.renderer),
+                Message::RendererChanged
+            ),
+            text("Try software rendering if the window stays blank. Requires a restart.")
+                .color([0.8, 0.8, 0.8])
+                .size(12),
+        ]
+        .spacing(5);
+
+        let mic_test = if self.mic_testing {
+            column![
+                row![
+                    level_meter("Raw level", self.raw_level),
+                    level_meter("Processed level", self.processed_level),
+                ]
+                .spacing(10),
+                aligned_button("Stop test").on_press(Message::ToggleMicTest),
+            ]
+            .spacing(5)
+        } else {
+            column![aligned_button("Test microphone").on_press(Message::ToggleMicTest)]
+        };
+
+        let assets_picker = column![
+            text("Assets folder:").size(14),
+            text(config.assets_path.display().to_string())
+                .color([0.8, 0.8, 0.8])
+                .size(12),
+            row![
+                aligned_button("Choose folder").on_press(Message::PickAssetsFolder),
+                aligned_button("Choose bundle").on_press(Message::PickAssetsBundle),
+            ]
+            .spacing(5),
+            text("Bundles are .chibi or .zip files holding a shared avatar.")
+                .color([0.8, 0.8, 0.8])
+                .size(12),
+        ]
+        .spacing(5);
+
+        let state_pickers = column![
+            image_picker(
+                "Idle image:",
+                &self.images,
+                config.closed_image_index,
+                Message::ClosedImageChanged
+            ),
+            image_picker(
+                "Talking image:",
+                &self.images,
+                config.open_image_index,
+                Message::OpenImageChanged
+            ),
+            text("Used when the avatar has no idle.png or talk.png.")
+                .color([0.8, 0.8, 0.8])
+                .size(12),
+        ]
+        .spacing(5);
+
+        let freeze_picker = column![
+            image_picker(
+                "Frozen pose:",
+                &self.images,
+                config.freeze_image,
+                Message::FreezeImageChanged
+            ),
+            text(format!(
+                "Image held while the avatar is frozen with '{}', e.g. a BRB pose.",
+                config.keybinds.freeze
+            ))
+            .color([0.8, 0.8, 0.8])
+            .size(12),
+        ]
+        .spacing(5);
+
+        let ui_hints = column![
+            text(format!(
+                "Press '{}' to show/hide UI elements",
+                config.keybinds.toggle_ui
+            ))
+            .color([0.8, 0.8, 0.8])
+            .size(12),
+            text(format!(
+                "Press '{}' to toggle chroma key",
+                config.keybinds.toggle_chroma_key
+            ))
+            .color([0.8, 0.8, 0.8])
+            .size(12),
+            text(format!(
+                "Press '{}' to mute the avatar",
+                config.keybinds.mute
+            ))
+            .color([0.8, 0.8, 0.8])
+            .size(12),
+            text(format!(
+                "Press '{}' to toggle fullscreen, showing only the avatar",
+                config.keybinds.toggle_fullscreen
+            ))
+            .color([0.8, 0.8, 0.8])
+            .size(12),
+            text(format!(
+                "Press '{}' to switch to the next profile",
+                config.keybinds.next_profile
+            ))
+            .color([0.8, 0.8, 0.8])
+            .size(12),
+            text(format!(
+                "Press '{}' to toggle mini mode",
+                config.keybinds.toggle_mini_mode
+            ))
+            .color([0.8, 0.8, 0.8])
+            .size(12),
+            text(format!(
+                "Press '{}' to show a graph of the recent input levels",
+                config.keybinds.toggle_debug_overlay
+            ))
+            .color([0.8, 0.8, 0.8])
+            .size(12),
+            text("Drag the avatar to move it within the window")
+                .color([0.8, 0.8, 0.8])
+                .size(12),
+            text("Keys can be changed in the [keybinds] section of config.toml")
+                .color([0.8, 0.8, 0.8])
+                .size(12),
+        ];
+
+        let level_db = amplitude_to_db(self.processed_level);
+        let clipping = self
+            .clipped_at
+            .is_some_and(|clipped_at| clipped_at.elapsed() < CLIP_HOLD);
+
+        let clip_indicator: Element<'_, Message> = if clipping {
+            row![
+                container(Space::new(Length::Fixed(10.0), Length::Fixed(10.0))).style(|_| {
+                    container::Style {
+                        background: Some(iced::Background::Color(iced::Color::from_rgb(
+                            1.0, 0.3, 0.3,
+                        ))),
+                        border: iced::Border::default().rounded(5),
+                        ..Default::default()
+                    }
+                }),
+                text("Clipping, lower the input gain")
+                    .size(12)
+                    .color([1.0, 0.3, 0.3]),
+            ]
+            .spacing(5)
+            .align_y(Alignment::Center)
+            .into()
+        } else {
+            Space::new(Length::Shrink, Length::Shrink).into()
+        };
+
+        let level = column![
+            row![
+                text(format!("Microphone level: {:.1} dB", level_db))
+                    .size(14)
+                    .width(Length::Fill),
+                clip_indicator,
+            ]
+            .align_y(Alignment::Center),
+            threshold_meter(level_db, config.microphone_threshold_db),
+            text("The red marker shows the current threshold.")
+                .color([0.8, 0.8, 0.8])
+                .size(12),
+        ]
+        .spacing(5);
+
+        let calibration_status = if self.calibration.is_some() {
+            "Calibrating, please stay quiet..."
+        } else {
+            self.calibration_result.as_deref().unwrap_or(
+                "Sample a few seconds of silence to pick a threshold above the ambient noise.",
+            )
+        };
+
+        let calibrate = column![
+            if self.calibration.is_some() {
+                aligned_button("Calibrate")
+            } else {
+                aligned_button("Calibrate").on_press(Message::CalibrateNoiseFloor)
+            },
+            text(calibration_status).color([0.8, 0.8, 0.8]).size(12),
+        ]
+        .spacing(5);
 
-    fn view_about<'a>(&self) -> Element<Message> {
+        let voice_filter_toggler = column![
+            toggler(config.voice_filter)
+                .label("Voice filter")
+                .on_toggle(Message::VoiceFilterChanged),
+            text("Ignores low rumble and high hiss that fall outside the range of the voice.")
+                .color([0.8, 0.8, 0.8])
+                .size(12),
+        ];
+
+        let detection = column![
+            level,
+            detection_picker,
+            threshold_slider,
+            calibrate,
+            deadband_slider,
+            hold_slider,
+            min_open_slider,
+            gain_slider,
+            voice_filter_toggler,
+        ]
+        .spacing(10);
+
+        // Noise suppression is only available when built with the `rnnoise` feature
+        #[cfg(feature = "rnnoise")]
+        let detection = detection.push(column![
+            toggler(config.noise_suppression)
+                .label("Noise suppression")
+                .on_toggle(Message::NoiseSuppressionChanged),
+            text("Removes background noise before detection, at the cost of some latency.")
+                .color([0.8, 0.8, 0.8])
+                .size(12),
+        ]);
+
+        let profile_picker = column![
+            text("Profile:").size(14),
+            pick_list(
+                config.profiles.keys().cloned().collect::<Vec<_>>(),
+                config.active_profile.clone(),
+                Message::ProfileChanged
+            ),
+            text("Profiles can be added in the [profiles] section of config.toml")
+                .color([0.8, 0.8, 0.8])
+                .size(12),
+        ]
+        .spacing(5);
+
+        let settings = column![
+            profile_picker,
+            detection,
+            flicker_toggler,
+            scale_slider,
+            bounce_slider,
+            crossfade_slider,
+            breathing_toggler,
+            blink_toggler,
+            chroma_color,
+            transparent_toggler,
+            mirror_toggler,
+            on_top_toggler,
+            mini_toggler,
+            monitor_picker,
+            theme_picker,
+            renderer_picker,
+            quit_toggler,
+            host_picker,
+            combo_input,
+            format_picker,
+            channel_picker,
+            monitor,
+            record_toggler,
+            assets_picker,
+            state_pickers,
+            freeze_picker,
+            mic_test,
+            ui_hints,
+            text(self.mic_status()).size(12),
+            aligned_button("Reset to defaults").on_press(Message::ResetConfig),
+        ]
+        .spacing(10);
+
+        let layout = column![
+            scrollable(settings).height(Length::Fill),
+            aligned_button("Back").on_press(Message::SwitchView(View::Home))
+        ]
+        .spacing(10)
+        .padding(15);
+
+        container(layout)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .into()
+    }
+
+    fn view_about(&self) -> Element<'_, Message> {
         let labels = column![
             text(format!("Chibi {}", APP_VERSION)).size(24),
             text("Indie PNG-tuber application made in Rust supporting all major platforms")
                 .size(12),
-            text("The example assets used in this application are created by @chereverie").size(12),
-            text("Licensed under the MPL-2.0 license").size(12),
+            link(PROJECT_URL, PROJECT_URL),
+            link(
+                "The example assets used in this application are created by @chereverie",
+                ASSETS_AUTHOR_URL
+            ),
+            link("Licensed under the MPL-2.0 license", LICENSE_URL),
+            text(self.speaking_stats()).size(12),
         ]
         .align_x(alignment::Horizontal::Center)
         .width(Length::Fill)
@@ -286,96 +1567,1653 @@ impl ChibiApp {
             .into()
     }
 
-    pub fn view(&self) -> Element<Message> {
-        match self.curr_view {
+    fn view_setup(&self, step: SetupStep) -> Element<'_, Message> {
+        let config = lock_and_unlock!(self.config);
+
+        let (number, title, content, previous, next) = match step {
+            SetupStep::Assets => {
+                let image_count = self.images.len() + self.named_images.len();
+
+                let content = column![
+                    text("Choose the folder with your avatar images, or a .chibi bundle. Name them 1.png (quiet) and 2.png (talking), or idle.png and talk.png.")
+                        .size(12),
+                    text(config.assets_path.display().to_string())
+                        .color([0.8, 0.8, 0.8])
+                        .size(12),
+                    text(format!("{} image(s) found", image_count))
+                        .color([0.8, 0.8, 0.8])
+                        .size(12),
+                    row![
+                        aligned_button("Choose folder").on_press(Message::PickAssetsFolder),
+                        aligned_button("Choose bundle").on_press(Message::PickAssetsBundle),
+                    ]
+                    .spacing(5),
+                ]
+                .spacing(5);
+
+                (1, "Avatar", content, None, Some(SetupStep::Microphone))
+            }
+            SetupStep::Microphone => {
+                let content = column![
+                    text("Pick the microphone to listen to. You can change this later in the settings.")
+                        .size(12),
+                    combo_box(
+                        &self.available_input_devices,
+                        "Input device",
+                        self.selected_input_device.as_ref(),
+                        Message::InputChanged,
+                    ),
+                    aligned_button("Refresh devices").on_press(Message::RefreshInputDevices),
+                ]
+                .spacing(5);
+
+                let content = match &self.device_notice {
+                    Some(notice) => content.push(text(notice).size(12).color([1.0, 0.6, 0.4])),
+                    None => content,
+                };
+
+                (
+                    2,
+                    "Microphone",
+                    content,
+                    Some(SetupStep::Assets),
+                    Some(SetupStep::Calibrate),
+                )
+            }
+            SetupStep::Calibrate => {
+                let calibration_status = if self.calibration.is_some() {
+                    "Calibrating, please stay quiet..."
+                } else {
+                    self.calibration_result.as_deref().unwrap_or(
+                        "Stay quiet and press Calibrate to set the threshold just above the noise in the room.",
+                    )
+                };
+
+                let content = column![
+                    threshold_meter(
+                        amplitude_to_db(self.processed_level),
+                        config.microphone_threshold_db
+                    ),
+                    if self.calibration.is_some() {
+                        aligned_button("Calibrate")
+                    } else {
+                        aligned_button("Calibrate").on_press(Message::CalibrateNoiseFloor)
+                    },
+                    text(calibration_status).color([0.8, 0.8, 0.8]).size(12),
+                ]
+                .spacing(5);
+
+                (3, "Threshold", content, Some(SetupStep::Microphone), None)
+            }
+        };
+
+        let back = match previous {
+            Some(previous) => {
+                aligned_button("Back").on_press(Message::SwitchView(View::Setup(previous)))
+            }
+            None => aligned_button("Back"),
+        };
+
+        let next = match next {
+            Some(next) => aligned_button("Next").on_press(Message::SwitchView(View::Setup(next))),
+            None => aligned_button("Finish").on_press(Message::FinishSetup),
+        };
+
+        let layout = column![
+            text("Welcome to chibi!").size(24),
+            text(format!("Step {} of 3: {}", number, title)).size(14),
+            content,
+            Space::new(Length::Fill, Length::Fill),
+            row![
+                back,
+                next,
+                aligned_button("Skip").on_press(Message::FinishSetup),
+            ]
+            .spacing(5),
+        ]
+        .spacing(10);
+
+        container(layout)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .padding(15)
+            .into()
+    }
+
+    fn view_confirm_quit(&self) -> Element<'_, Message> {
+        let layout = column![
+            text("Are you sure you want to quit?").size(14),
+            Space::new(Length::Fill, Length::Fill),
+            row![
+                aligned_button("Quit").on_press(Message::ConfirmQuit),
+                aligned_button("Cancel").on_press(Message::CancelQuit),
+            ]
+            .spacing(5)
+        ]
+        .align_x(Alignment::Center)
+        .spacing(10);
+
+        container(layout)
+            .width(Length::Fill)
+            .height(Length::Fill)
+            .padding(15)
+            .into()
+    }
+
+    pub fn view(&self) -> Element<'_, Message> {
+        if self.quit_requested {
+            return self.view_confirm_quit();
+        }
+
+        let content = match self.curr_view {
             View::Home => self.view_home(),
             View::Settings => self.view_settings(),
             View::About => self.view_about(),
-        }
+            View::Setup(step) => self.view_setup(step),
+        };
+
+        let Some((notification, _)) = &self.notification else {
+            return content;
+        };
+
+        // Shown on top of the view, so the avatar doesn't move around
+        let banner = container(
+            row![
+                text(notification).size(12).width(Length::Fill),
+                button(text("Dismiss").size(12))
+                    .padding(5)
+                    .on_press(Message::DismissNotification),
+            ]
+            .spacing(10)
+            .align_y(Alignment::Center),
+        )
+        .width(Length::Fill)
+        .padding(10)
+        .style(container::dark);
+
+        stack![content, container(banner).padding(15)].into()
     }
 
-    pub fn update(&mut self, message: Message) {
+    pub fn update(&mut self, message: Message) -> Task<Message> {
         let mut config = lock_and_unlock!(self.config);
 
         match message {
-            Message::MicActive(active) => {
-                if active {
-                    self.curr_image = Some(self.get_image(1).unwrap().clone());
+            // A frozen avatar holds its pose whatever the microphone does
+            Message::MicActive(event) => {
+                let active = event.active;
+
+                // A frozen avatar holds its pose whatever the microphone does, and doesn't
+                // count as talking. Levels, stats and outputs keep updating
+                let frozen = self.frozen_image.is_some();
+                self.talking_tier = if active && !self.muted && !frozen {
+                    // Each loudness threshold the level reaches moves up to the next image
+                    let level_db = amplitude_to_db(event.rms);
+                    let tier = config
+                        .loudness_thresholds_db
+                        .iter()
+                        .filter(|&&threshold_db| level_db >= threshold_db)
+                        .count();
+
+                    Some(tier)
                 } else {
-                    self.curr_image = Some(self.get_image(0).unwrap().clone());
+                    None
+                };
+
+                if active != self.mic_activated
+                    && let Some(websocket) = &self.websocket
+                {
+                    websocket.broadcast(&event);
+                }
+
+                if let Some(metrics) = &self.metrics {
+                    metrics.update(&event);
+                }
+
+                let image = self.current_image().cloned();
+
+                // Fade out the old image when the talking state changes the image
+                if config.crossfade_ms > 0
+                    && let Some(old_image) = &self.curr_image
+                    && image
+                        .as_ref()
+                        .is_some_and(|image| image.id() != old_image.id())
+                {
+                    self.fading_image = Some((old_image.clone(), Instant::now()));
+                }
+
+                // Add up the time between becoming active and inactive again
+                let talking = active && !frozen;
+                if talking && self.talking_since.is_none() {
+                    self.talking_since = Some(Instant::now());
+                } else if !talking && let Some(since) = self.talking_since.take() {
+                    let talked = since.elapsed();
+                    self.speaking_time += talked;
+
+                    // Saved along with everything else when quitting
+                    config.add_speaking_time(talked);
                 }
 
+                self.curr_image = image;
                 self.mic_activated = active;
+                self.raw_level = event.raw_rms;
+                self.processed_level = event.rms;
+
+                if self.debug_overlay {
+                    if self.level_history.len() == LEVEL_HISTORY_LEN {
+                        self.level_history.pop_front();
+                    }
+                    self.level_history
+                        .push_back((amplitude_to_db(event.rms), active));
+                }
+
+                if event.clipping {
+                    self.clipped_at = Some(Instant::now());
+                }
+
+                if let Some(calibration) = &mut self.calibration {
+                    calibration.readings.push(event.rms);
+                }
             }
-            Message::ThresholdChanged(threshold) => {
-                config.microphone_threshold = threshold;
+            Message::CalibrationTick(now) => {
+                // Ended by a timer rather than by the next reading, so calibration still
+                // finishes if the device stops sending anything
+                if let Some(calibration) = &self.calibration
+                    && now.duration_since(calibration.started) >= CALIBRATION_DURATION
+                {
+                    let readings = self
+                        .calibration
+                        .take()
+                        .map(|calibration| calibration.readings)
+                        .unwrap_or_default();
+
+                    if readings.is_empty() {
+                        self.calibration_result = Some(
+                            "No audio was received from the microphone, check the input device."
+                                .to_string(),
+                        );
+                    } else {
+                        let average = readings.iter().sum::<f32>() / readings.len() as f32;
+                        let peak = readings.iter().copied().fold(0.0, f32::max);
+
+                        // Clamp so an already loud room doesn't produce a threshold that
+                        // can never be reached (or a quiet one that is always exceeded)
+                        let threshold = (peak * CALIBRATION_MARGIN)
+                            .clamp(*CALIBRATION_RANGE.start(), *CALIBRATION_RANGE.end());
+
+                        config.microphone_threshold_db =
+                            (amplitude_to_db(threshold) * 10.0).round() / 10.0;
+                        config.save();
+
+                        self.calibration_result = Some(format!(
+                            "Ambient noise: {:.1} dB average, {:.1} dB peak. Threshold set to {:.1} dB",
+                            amplitude_to_db(average),
+                            amplitude_to_db(peak),
+                            config.microphone_threshold_db
+                        ));
+                    }
+                }
+            }
+            Message::ThresholdChanged(threshold_db) => {
+                config.microphone_threshold_db = threshold_db;
+                config.save();
+            }
+            Message::DetectionModeChanged(mode) => {
+                config.detection_mode = mode;
+                config.save();
+            }
+            Message::DeadbandModeChanged(mode) => {
+                // Start out from the level the current mode deactivates at
+                if mode == DeadbandMode::Absolute {
+                    config.off_threshold_db = (config.threshold_off_db() * 10.0).round() / 10.0;
+                }
+
+                config.deadband_mode = mode;
+                config.save();
+            }
+            Message::OffThresholdChanged(off_threshold_db) => {
+                config.off_threshold_db = off_threshold_db;
                 config.save();
             }
             Message::DeadbandChanged(deadband) => {
                 config.deadband_factor = deadband;
                 config.save();
             }
-            Message::SwitchView(view) => {
-                self.curr_view = view;
+            Message::MinOpenChanged(min_open_ms) => {
+                config.min_open_ms = min_open_ms;
+                config.save();
             }
-            Message::InputChanged(device) => {
-                self.selected_input_device = Some(device.clone());
-                self.show_modal = true;
+            Message::HoldTimeChanged(hold_time_ms) => {
+                config.hold_time_ms = hold_time_ms;
+                config.save();
             }
-            Message::FlickerChanged(flicker) => {
-                config.flicker_input = flicker;
+            Message::GainChanged(gain) => {
+                config.input_gain = gain;
                 config.save();
             }
-            Message::AppEvent(event) => {
-                if let Event::Keyboard(iced::keyboard::Event::KeyPressed { key, .. }) = event {
-                    match key {
-                        Key::Named(Named::Escape) => {
-                            self.show_buttons = !self.show_buttons;
-                        }
-                        Key::Character(c) if c == "c" => {
-                            self.chroma_key = !self.chroma_key;
-                        }
-                        _ => {}
+            Message::VoiceFilterChanged(voice_filter) => {
+                config.voice_filter = voice_filter;
+                config.save();
+            }
+            Message::NoiseSuppressionChanged(noise_suppression) => {
+                config.noise_suppression = noise_suppression;
+                config.save();
+            }
+            Message::ResetConfig => {
+                // Keep the window where it is, along with what isn't a setting (profiles,
+                // keybinds and stats). Everything else goes back to the defaults
+                let mut defaults = ChibiConfig::default();
+                defaults.window_width = config.window_width;
+                defaults.window_height = config.window_height;
+                defaults.window_position = config.window_position;
+                defaults.profiles = std::mem::take(&mut config.profiles);
+                defaults.keybinds = config.keybinds.clone();
+                defaults.total_speaking_ms = config.total_speaking_ms;
+
+                let was_mini_mode = config.mini_mode;
+                *config = defaults;
+                config.save();
+
+                let assets_path = config.assets_path.clone();
+                let always_on_top = config.always_on_top;
+                let size = iced::Size::new(config.window_width, config.window_height);
+
+                // Loading images and devices locks the config again. The default host and
+                // device may differ from the ones in use, so capture starts over
+                drop(config);
+                self.load_images(&assets_path);
+                self.curr_image = None;
+                self.load_input_devices();
+                self.restart_capture();
+
+                return window::get_latest().and_then(move |id| {
+                    let level = window::change_level(id, window_level(always_on_top));
+
+                    // Leave mini mode, which the defaults have off
+                    if was_mini_mode {
+                        Task::batch([
+                            level,
+                            window::toggle_decorations(id),
+                            window::resize(id, size),
+                        ])
+                    } else {
+                        level
                     }
-                }
+                });
             }
-        }
-    }
+            Message::ToggleMiniMode => {
+                config.mini_mode = !config.mini_mode;
+                config.save();
 
-    pub fn subscription(&self) -> iced::Subscription<Message> {
-        // Subscribe to application events
-        event::listen().map(Message::AppEvent)
-    }
-}
+                // The regular size is left alone while in mini mode, so it can be restored
+                let size = if config.mini_mode {
+                    self.curr_view = View::Home;
+                    self.update_levels_wanted();
+                    iced::Size::new(config.mini_size, config.mini_size)
+                } else {
+                    iced::Size::new(config.window_width, config.window_height)
+                };
 
-impl ChibiApp {
-    pub fn new(config: ChibiConfig, receiver: Option<Receiver<bool>>) -> Self {
-        Self {
-            config: Arc::new(Mutex::new(config)),
-            receiver,
-            ..Default::default()
-        }
-    }
+                return window::get_latest().and_then(move |id| {
+                    Task::batch([window::toggle_decorations(id), window::resize(id, size)])
+                });
+            }
+            Message::ToggleMute => {
+                self.muted = !self.muted;
 
-    pub fn load_images(&mut self, path: &Path) {
-        let images = std::fs::read_dir(path)
-            .expect("Failed to read directory")
-            .map(|entry| {
-                let entry = entry.expect("Failed to read entry");
-                let path = entry.path();
+                // Close the mouth straight away rather than on the next detection event
+                if self.muted {
+                    self.talking_tier = None;
+                }
 
-                Handle::from_path(path)
-            })
-            .collect();
+                if self.curr_image.is_some() {
+                    self.curr_image = self.current_image().cloned();
+                }
+            }
+            Message::ToggleDebugOverlay => {
+                self.debug_overlay = !self.debug_overlay;
+                self.level_history.clear();
 
-        self.set_images(images);
-    }
+                drop(config);
+                self.update_levels_wanted();
 
-    pub fn set_images(&mut self, images: Vec<Handle>) {
-        self.images = Arc::new(images);
-    }
+                return Task::none();
+            }
+            Message::ToggleFreeze => {
+                self.frozen_image = match self.frozen_image {
+                    Some(_) => None,
+                    None => Some(config.freeze_image),
+                };
 
-    pub fn get_image(&self, index: usize) -> Option<&Handle> {
-        self.images.get(index)
-    }
+                // Time spent frozen isn't talking, so end a stretch that is still going on
+                if self.frozen_image.is_some()
+                    && let Some(since) = self.talking_since.take()
+                {
+                    let talked = since.elapsed();
+                    self.speaking_time += talked;
+                    config.add_speaking_time(talked);
+                }
+
+                // Pick up the microphone state again from the next detection event
+                self.talking_tier = None;
+                self.fading_image = None;
+                self.curr_image = self.current_image().cloned();
+            }
+            Message::FreezeImageChanged(ImageChoice(index)) => {
+                config.freeze_image = index;
+                config.save();
+            }
+            Message::ClosedImageChanged(ImageChoice(index)) => {
+                config.closed_image_index = index;
+                config.save();
+
+                self.closed_image = index;
+                self.curr_image = self.current_image().cloned();
+            }
+            Message::OpenImageChanged(ImageChoice(index)) => {
+                config.open_image_index = index;
+                config.save();
+
+                self.open_image = index;
+                self.curr_image = self.current_image().cloned();
+            }
+            Message::ToggleChromaKey => {
+                config.chroma_key = !config.chroma_key;
+                config.save();
+            }
+            #[cfg(feature = "tray")]
+            Message::MinimizeToTrayChanged(minimize_to_tray) => {
+                config.minimize_to_tray = minimize_to_tray;
+                config.save();
+            }
+            #[cfg(feature = "tray")]
+            Message::ShowWindow => {
+                return window::get_latest().and_then(|id| {
+                    window::change_mode(id, window::Mode::Windowed).chain(window::gain_focus(id))
+                });
+            }
+            #[cfg(feature = "ndi")]
+            Message::CaptureNdiFrame => {
+                return window::get_latest()
+                    .and_then(window::screenshot)
+                    .map(Message::NdiFrameCaptured);
+            }
+            #[cfg(feature = "ndi")]
+            Message::NdiFrameCaptured(frame) => {
+                if let Some(ndi) = &self.ndi {
+                    ndi.push(frame);
+                }
+            }
+            #[cfg(feature = "tray")]
+            Message::PollTray => {
+                return Task::batch(Tray::poll_actions().into_iter().map(|action| {
+                    Task::done(match action {
+                        TrayAction::Show => Message::ShowWindow,
+                        TrayAction::Mute => Message::ToggleMute,
+                        TrayAction::ToggleChromaKey => Message::ToggleChromaKey,
+                        // Quitting from the tray is deliberate, so skip the confirmation
+                        TrayAction::Quit => Message::ConfirmQuit,
+                    })
+                }));
+            }
+            Message::CalibrateNoiseFloor => {
+                self.calibration = Some(Calibration {
+                    started: Instant::now(),
+                    readings: vec![],
+                });
+                self.calibration_result = None;
+            }
+            Message::AvatarScaleChanged(scale) => {
+                config.avatar_scale = scale;
+                config.save();
+            }
+            Message::BounceIntensityChanged(intensity) => {
+                config.bounce_intensity = intensity;
+                config.save();
+            }
+            Message::MirrorChanged(mirror) => {
+                config.mirror_horizontal = mirror;
+                config.save();
+
+                // Images are flipped when loading, so reload them. This locks the config again
+                let assets_path = config.assets_path.clone();
+                drop(config);
+                self.load_images(&assets_path);
+                self.curr_image = None;
+
+                return Task::none();
+            }
+            Message::WindowMonitorChanged(monitor) => {
+                config.window_monitor = Some(monitor.index);
+                config.save();
+
+                let window_size = if config.mini_mode {
+                    iced::Size::new(config.mini_size, config.mini_size)
+                } else {
+                    iced::Size::new(config.window_width, config.window_height)
+                };
+                let position = monitor.centered(window_size);
+
+                return window::get_latest().and_then(move |id| window::move_to(id, position));
+            }
+            Message::ChromaMatteChanged(matte) => {
+                config.chroma_matte = matte;
+                config.save();
+
+                // Edges are sharpened when loading too
+                let assets_path = config.assets_path.clone();
+                drop(config);
+                self.load_images(&assets_path);
+                self.curr_image = None;
+
+                return Task::none();
+            }
+            Message::AvatarPressed => {
+                self.drag_start = Some((self.cursor_position, config.avatar_offset));
+            }
+            Message::AvatarMoved(position) => {
+                self.cursor_position = position;
+
+                if let Some((start, [x, y])) = self.drag_start {
+                    config.avatar_offset = [x + position.x - start.x, y + position.y - start.y];
+                }
+            }
+            Message::AvatarReleased => {
+                // Only save once the drag is over, rather than on every movement
+                if self.drag_start.take().is_some() {
+                    config.save();
+                }
+            }
+            Message::ChromaColorChanged(color) => {
+                config.chroma_key_color = color;
+                config.save();
+            }
+            Message::TransparentBackgroundChanged(transparent) => {
+                config.transparent_background = transparent;
+                config.save();
+            }
+            Message::AlwaysOnTopChanged(always_on_top) => {
+                config.always_on_top = always_on_top;
+                config.save();
+
+                return window::get_latest()
+                    .and_then(move |id| window::change_level(id, window_level(always_on_top)));
+            }
+            Message::PickAssetsFolder => {
+                let folder = rfd::AsyncFileDialog::new()
+                    .set_title("Choose assets folder")
+                    .set_directory(&config.assets_path)
+                    .pick_folder();
+
+                return Task::perform(folder, |folder| {
+                    Message::AssetsPathChanged(folder.map(|folder| folder.path().to_path_buf()))
+                });
+            }
+            Message::PickAssetsBundle => {
+                let bundle = rfd::AsyncFileDialog::new()
+                    .set_title("Choose avatar bundle")
+                    .add_filter("Avatar bundle", BUNDLE_EXTENSIONS)
+                    .pick_file();
+
+                return Task::perform(bundle, |bundle| {
+                    Message::AssetsPathChanged(bundle.map(|bundle| bundle.path().to_path_buf()))
+                });
+            }
+            Message::AssetsPathChanged(Some(path)) => {
+                config.assets_path = path.clone();
+                config.save();
+
+                // Loading images locks the config again
+                drop(config);
+                self.load_images(&path);
+                self.curr_image = None;
+
+                // Start out at the size the avatar was made for
+                if let Some(scale) = self.avatar_scale {
+                    let mut config = lock_and_unlock!(self.config);
+                    config.avatar_scale = scale;
+                    config.validate();
+                    config.save();
+                }
+
+                return Task::none();
+            }
+            Message::AssetsPathChanged(None) => {}
+            Message::ShowNotification(notification) => {
+                self.notification = Some((notification, Instant::now()));
+            }
+            Message::DismissNotification => {
+                self.notification = None;
+            }
+            Message::NotificationTick(now) => {
+                if self
+                    .notification
+                    .as_ref()
+                    .is_some_and(|(_, shown)| now.duration_since(*shown) >= NOTIFICATION_DURATION)
+                {
+                    self.notification = None;
+                }
+            }
+            Message::OpenUrl(url) => {
+                if let Err(err) = open::that_detached(url) {
+                    warn!("Failed to open {}: {}", url, err);
+                }
+            }
+            Message::ProfileChanged(name) => {
+                if !config.switch_profile(&name) {
+                    return Task::none();
+                }
+                config.save();
+
+                let assets_path = config.assets_path.clone();
+
+                // Loading images locks the config again
+                drop(config);
+                self.load_images(&assets_path);
+                self.curr_image = None;
+
+                return Task::none();
+            }
+            Message::ToggleMicTest => {
+                self.mic_testing = !self.mic_testing;
+            }
+            Message::FinishSetup => {
+                // Write out the initial config, so setup isn't shown again
+                config.save();
+
+                self.calibration = None;
+                self.calibration_result = None;
+                self.curr_view = View::Home;
+                self.update_levels_wanted();
+            }
+            Message::SwitchView(view) => {
+                self.curr_view = view;
+                self.update_levels_wanted();
+            }
+            Message::InputChanged(device) => match device.raw_device.default_input_config() {
+                Ok(input_config) => {
+                    // Remember the device for the next launch. Formats differ between
+                    // devices, so start out with the new one's default
+                    config.input_device = Some(device.friendly_name.clone());
+                    config.input_format = None;
+                    config.save();
+
+                    self.device_notice = None;
+                    self.stream_formats = capture::get_stream_formats(&device.raw_device);
+                    self.selected_input_device = Some(device);
+                    self.selected_input_config = Some(input_config);
+
+                    // The capture thread locks the config too
+                    drop(config);
+                    self.restart_capture();
+
+                    return Task::none();
+                }
+                Err(err) => {
+                    warn!("Failed to get the input config for {}: {}", device, err);
+                }
+            },
+            Message::RefreshInputDevices => {
+                let host = config.audio_host.clone();
+                let devices = capture::get_input_devices(host.as_deref());
+
+                // Start capturing as soon as a microphone shows up
+                let default_device = capture::find_default_device(&devices, host.as_deref());
+                self.available_input_devices = combo_box::State::new(devices);
+                self.output_devices = capture::get_output_device_names(host.as_deref());
+
+                if self.selected_input_device.is_none()
+                    && let Some(device) = default_device
+                {
+                    return Task::done(Message::InputChanged(device));
+                }
+            }
+            Message::AudioHostChanged(host) => {
+                config.audio_host = Some(host);
+                config.input_device = None;
+                config.input_format = None;
+                config.monitor_device = None;
+                config.save();
+
+                // Devices belong to a host, so start over with the new host's default device
+                drop(config);
+                self.load_input_devices();
+                self.restart_capture();
+
+                return Task::none();
+            }
+            Message::MonitorChanged(enabled) => {
+                config.monitor_enabled = enabled;
+                config.save();
+
+                // The output stream is opened along with the input stream
+                drop(config);
+                self.restart_capture();
+
+                return Task::none();
+            }
+            Message::RecordChanged(record) => {
+                config.record_to_wav = record;
+                config.save();
+
+                // Recordings start and end with the capture thread
+                drop(config);
+                self.restart_capture();
+
+                return Task::none();
+            }
+            Message::MonitorDeviceChanged(device) => {
+                config.monitor_device = Some(device);
+                config.save();
+
+                let enabled = config.monitor_enabled;
+                drop(config);
+                if enabled {
+                    self.restart_capture();
+                }
+
+                return Task::none();
+            }
+            Message::StreamFormatChanged(format) => {
+                let Some(device) = &self.selected_input_device else {
+                    return Task::none();
+                };
+
+                match capture::get_stream_config(&device.raw_device, format) {
+                    Ok(input_config) => {
+                        config.input_format = format;
+                        config.save();
+
+                        self.selected_input_config = Some(input_config);
+
+                        // The capture thread locks the config too
+                        drop(config);
+                        self.restart_capture();
+
+                        return Task::none();
+                    }
+                    Err(err) => {
+                        warn!("Failed to get the input config for {}: {}", device, err);
+                    }
+                }
+            }
+            Message::ChannelModeChanged(channel_mode) => {
+                config.channel_mode = channel_mode;
+                config.save();
+            }
+            Message::ThemeChanged(theme) => {
+                config.theme = theme.to_string();
+                config.save();
+            }
+            Message::RendererChanged(renderer) => {
+                config.renderer = renderer;
+                config.save();
+            }
+            // Moving one end of the range past the other drags it along
+            Message::FlickerMinChanged(min_ms) => {
+                config.flicker_min_ms = min_ms;
+                config.flicker_max_ms = config.flicker_max_ms.max(min_ms);
+                config.save();
+            }
+            Message::FlickerMaxChanged(max_ms) => {
+                config.flicker_max_ms = max_ms;
+                config.flicker_min_ms = config.flicker_min_ms.min(max_ms);
+                config.save();
+            }
+            Message::FlickerChanged(flicker) => {
+                config.flicker_input = flicker;
+                config.save();
+            }
+            Message::BreathingChanged(breathing) => {
+                config.breathing_enabled = breathing;
+                config.save();
+
+                self.breathing_scale = 1.0;
+            }
+            Message::BlinkChanged(blink) => {
+                config.blink_enabled = blink;
+                config.save();
+
+                self.blinking_until = None;
+                self.next_blink = Instant::now();
+            }
+            Message::ConfirmQuitChanged(confirm) => {
+                config.confirm_quit = confirm;
+                config.save();
+            }
+            Message::AppEvent(event) => match event {
+                Event::Keyboard(iced::keyboard::Event::KeyPressed { key, .. }) => {
+                    match config.keybinds.action_for(&key) {
+                        Some(KeyAction::ToggleUi) => {
+                            config.show_buttons = !config.show_buttons;
+                            config.save();
+                        }
+                        Some(KeyAction::ToggleChromaKey) => {
+                            return Task::done(Message::ToggleChromaKey);
+                        }
+                        Some(KeyAction::Mute) => {
+                            return Task::done(Message::ToggleMute);
+                        }
+                        Some(KeyAction::Freeze) => {
+                            return Task::done(Message::ToggleFreeze);
+                        }
+                        Some(KeyAction::ToggleDebugOverlay) => {
+                            return Task::done(Message::ToggleDebugOverlay);
+                        }
+                        Some(KeyAction::ToggleMiniMode) => {
+                            return Task::done(Message::ToggleMiniMode);
+                        }
+                        Some(KeyAction::NextProfile) => {
+                            if let Some(name) = config.next_profile() {
+                                return Task::done(Message::ProfileChanged(name));
+                            }
+                        }
+                        Some(KeyAction::ToggleFullscreen) => {
+                            self.fullscreen = !self.fullscreen;
+
+                            let mode = if self.fullscreen {
+                                self.curr_view = View::Home;
+                                self.update_levels_wanted();
+                                window::Mode::Fullscreen
+                            } else {
+                                window::Mode::Windowed
+                            };
+
+                            return window::get_latest()
+                                .and_then(move |id| window::change_mode(id, mode));
+                        }
+                        None => {}
+                    }
+                }
+                // Remember the window geometry for the next launch, unless it only changed
+                // because of fullscreen or mini mode
+                Event::Window(window::Event::Resized(size))
+                    if !self.fullscreen && !config.mini_mode =>
+                {
+                    // Dragging sends a stream of these, so saving waits until it is over
+                    config.window_width = size.width;
+                    config.window_height = size.height;
+                    self.geometry_changed = Some(Instant::now());
+                }
+                Event::Window(window::Event::Moved(position)) if !self.fullscreen => {
+                    config.window_position = Some([position.x, position.y]);
+                    self.geometry_changed = Some(Instant::now());
+                }
+                _ => {}
+            },
+            Message::CloseRequested => {
+                // Keep detection running in the background, the window can be brought back
+                // from the tray
+                #[cfg(feature = "tray")]
+                if config.minimize_to_tray && self.tray.is_some() {
+                    return window::get_latest()
+                        .and_then(|id| window::change_mode(id, window::Mode::Hidden));
+                }
+
+                if !config.confirm_quit {
+                    // The capture thread locks the config, so release it before joining
+                    drop(config);
+                    return self.shutdown();
+                }
+
+                self.quit_requested = true;
+            }
+            Message::ConfirmQuit => {
+                drop(config);
+                return self.shutdown();
+            }
+            Message::CancelQuit => {
+                self.quit_requested = false;
+            }
+            Message::GeometrySaveTick(now) => {
+                if self
+                    .geometry_changed
+                    .is_some_and(|changed| now.duration_since(changed) >= GEOMETRY_SAVE_DELAY)
+                {
+                    self.geometry_changed = None;
+                    config.save();
+                }
+            }
+            Message::CrossfadeTick(now) => {
+                let duration = Duration::from_millis(config.crossfade_ms as u64);
+
+                if self
+                    .fading_image
+                    .as_ref()
+                    .is_some_and(|(_, started)| now.duration_since(*started) >= duration)
+                {
+                    self.fading_image = None;
+                }
+            }
+            Message::CrossfadeChanged(crossfade_ms) => {
+                config.crossfade_ms = crossfade_ms;
+                config.save();
+            }
+            Message::AnimationTick(now) => {
+                // Blend the breathing out while talking, and back in once idle
+                let target = if self.mic_activated { 0.0 } else { 1.0 };
+                self.breathing_weight += (target - self.breathing_weight) * 0.1;
+
+                let elapsed = now.duration_since(self.animation_start).as_secs_f32();
+                let period = config.breathing_period_ms.max(1) as f32 / 1000.0;
+                let breath = (1.0 - (elapsed * TAU / period).cos()) / 2.0;
+
+                self.breathing_scale =
+                    1.0 - config.breathing_amplitude * self.breathing_weight * breath;
+            }
+            Message::FrameTick(_) => {
+                self.frame_index = self.frame_index.wrapping_add(1);
+
+                if self.curr_image.is_some() {
+                    self.curr_image = self.current_image().cloned();
+                }
+            }
+            Message::BlinkTick(now) => {
+                let was_blinking = self.blinking_until.is_some();
+
+                if self.blinking_until.is_some_and(|until| now >= until) {
+                    // Schedule the next blink at a random interval
+                    let min_ms = config.blink_interval_min_ms;
+                    let max_ms = config.blink_interval_max_ms.max(min_ms);
+                    let interval = rand::rng().random_range(min_ms..=max_ms);
+
+                    self.blinking_until = None;
+                    self.next_blink = now + Duration::from_millis(interval as u64);
+                } else if self.blinking_until.is_none() && now >= self.next_blink {
+                    self.blinking_until = Some(now + BLINK_DURATION);
+                }
+
+                // Only swap images once detection has started, so the splash image stays up
+                if was_blinking != self.blinking_until.is_some() && self.curr_image.is_some() {
+                    self.curr_image = self.current_image().cloned();
+                }
+            }
+        }
+
+        Task::none()
+    }
+
+    /// Extra scale applied to the avatar while talking, growing with how far the level is
+    /// above the threshold
+    fn bounce_scale(&self, config: &ChibiConfig) -> f32 {
+        if !self.mic_activated || self.muted {
+            return 1.0;
+        }
+
+        let above_db = amplitude_to_db(self.processed_level) - config.microphone_threshold_db;
+        1.0 + config.bounce_intensity * (above_db / BOUNCE_RANGE_DB).clamp(0.0, 1.0)
+    }
+
+    pub fn theme(&self) -> Theme {
+        theme_by_name(&lock_and_unlock!(self.config).theme)
+    }
+
+    pub fn style(&self, theme: &Theme) -> Appearance {
+        let appearance = theme.default_style();
+
+        // Let the desktop show through behind the avatar, unless chroma key is on
+        let showing_avatar = matches!(self.curr_view, View::Home) && !self.quit_requested;
+        if self.transparent_window && !lock_and_unlock!(self.config).chroma_key && showing_avatar {
+            Appearance {
+                background_color: iced::Color::TRANSPARENT,
+                ..appearance
+            }
+        } else {
+            appearance
+        }
+    }
+
+    pub fn subscription(&self) -> Subscription<Message> {
+        let config = lock_and_unlock!(self.config);
+
+        // Only redraw every frame while there is something to animate
+        let animation = if config.breathing_enabled {
+            window::frames().map(Message::AnimationTick)
+        } else {
+            Subscription::none()
+        };
+
+        // Redraw every frame while fading between images
+        let crossfade = if self.fading_image.is_some() {
+            window::frames().map(Message::CrossfadeTick)
+        } else {
+            Subscription::none()
+        };
+
+        // Advance animated states only when there are frames to advance through
+        let frames = if self.state_frames.values().any(|frames| frames.len() > 1) {
+            let frame_duration_ms = self
+                .avatar_frame_duration_ms
+                .unwrap_or(config.frame_duration_ms);
+            let frame_duration = Duration::from_millis(frame_duration_ms.max(1) as u64);
+            iced::time::every(frame_duration).map(Message::FrameTick)
+        } else {
+            Subscription::none()
+        };
+
+        let notification = if self.notification.is_some() {
+            iced::time::every(Duration::from_secs(1)).map(Message::NotificationTick)
+        } else {
+            Subscription::none()
+        };
+
+        let blink = if config.blink_enabled {
+            iced::time::every(Duration::from_millis(50)).map(Message::BlinkTick)
+        } else {
+            Subscription::none()
+        };
+
+        let calibration = if self.calibration.is_some() {
+            iced::time::every(Duration::from_millis(250)).map(Message::CalibrationTick)
+        } else {
+            Subscription::none()
+        };
+
+        let geometry_save = if self.geometry_changed.is_some() {
+            iced::time::every(GEOMETRY_SAVE_DELAY / 2).map(Message::GeometrySaveTick)
+        } else {
+            Subscription::none()
+        };
+
+        // Subscribe to application events and intercept closing the window
+        // Menu events from the tray icon have to be polled for, if the icon was created
+        #[cfg(feature = "tray")]
+        let tray = if self.tray.is_some() {
+            iced::time::every(Duration::from_millis(100)).map(|_| Message::PollTray)
+        } else {
+            Subscription::none()
+        };
+        #[cfg(not(feature = "tray"))]
+        let tray = Subscription::none();
+
+        // The window is captured for NDI at the configured frame rate while publishing
+        #[cfg(feature = "ndi")]
+        let ndi = if self.ndi.is_some() {
+            let frame_interval = Duration::from_secs(1) / config.ndi_fps.clamp(1, 60);
+            iced::time::every(frame_interval).map(|_| Message::CaptureNdiFrame)
+        } else {
+            Subscription::none()
+        };
+        #[cfg(not(feature = "ndi"))]
+        let ndi = Subscription::none();
+
+        Subscription::batch([
+            // Keys typed into a widget, e.g. the device search, aren't meant as hotkeys
+            event::listen_with(|event, status, _window| match (&event, status) {
+                (Event::Keyboard(_), event::Status::Captured) => None,
+                _ => Some(Message::AppEvent(event)),
+            }),
+            window::close_requests().map(|_| Message::CloseRequested),
+            animation,
+            crossfade,
+            frames,
+            blink,
+            notification,
+            geometry_save,
+            calibration,
+            tray,
+            ndi,
+        ])
+    }
+
+    /// Stop capturing, persist state and exit the application
+    ///
+    /// Must be called without holding the config lock, since the capture thread takes it
+    /// while it is being stopped
+    fn shutdown(&mut self) -> Task<Message> {
+        // Stops the stream and waits for the capture thread to finish, so the input device
+        // is released and any recording is finalised before exiting
+        self.capture_session = None;
+
+        // Close overlay connections cleanly and stop listening
+        self.websocket = None;
+        self.metrics = None;
+
+        #[cfg(feature = "ndi")]
+        {
+            self.ndi = None;
+        }
+
+        let mut config = lock_and_unlock!(self.config);
+
+        // Count talking that was still going on when quitting
+        if let Some(since) = self.talking_since.take() {
+            config.add_speaking_time(since.elapsed());
+        }
+
+        config.save();
+        debug!("Capture stopped and config saved, exiting");
+
+        iced::exit()
+    }
+}
+
+impl ChibiApp {
+    pub fn new(config: ChibiConfig, receiver: Option<Receiver<MicEvent>>) -> Self {
+        Self {
+            config: Arc::new(Mutex::new(config)),
+            receiver,
+            ..Default::default()
+        }
+    }
+
+    /// List the input devices of the configured audio host, and select its default device.
+    /// There may be no input device at all, in which case the app runs without a microphone
+    pub fn load_input_devices(&mut self) {
+        let host = lock_and_unlock!(self.config).audio_host.clone();
+
+        let devices = capture::get_input_devices(host.as_deref());
+
+        self.selected_input_device = capture::find_default_device(&devices, host.as_deref());
+        self.available_input_devices = combo_box::State::new(devices);
+        self.output_devices = capture::get_output_device_names(host.as_deref());
+        self.selected_input_config = self
+            .selected_input_device
+            .as_ref()
+            .and_then(|device| device.raw_device.default_input_config().ok());
+        self.stream_formats = self
+            .selected_input_device
+            .as_ref()
+            .map(|device| capture::get_stream_formats(&device.raw_device))
+            .unwrap_or_default();
+    }
+
+    /// Select the input device saved in the config, falling back to the default device
+    /// (with a notice) if it is no longer available
+    pub fn restore_input_device(&mut self) {
+        let config = lock_and_unlock!(self.config).clone();
+        let Some(name) = config.input_device else {
+            return;
+        };
+
+        let device = self
+            .available_input_devices
+            .options()
+            .iter()
+            .find(|device| device.friendly_name == name)
+            .or_else(|| {
+                // Friendly names can change between versions, so also accept the raw name
+                self.available_input_devices
+                    .options()
+                    .iter()
+                    .find(|device| device.raw_device.name().is_ok_and(|raw| raw == name))
+            })
+            .cloned();
+
+        match device.map(|device| {
+            let input_config = capture::get_stream_config(&device.raw_device, config.input_format);
+            (input_config, device)
+        }) {
+            Some((Ok(input_config), device)) => {
+                self.stream_formats = capture::get_stream_formats(&device.raw_device);
+                self.selected_input_device = Some(device);
+                self.selected_input_config = Some(input_config);
+            }
+            _ => {
+                warn!(
+                    "Input device '{}' not found, using the default device",
+                    name
+                );
+                self.device_notice = Some(format!(
+                    "Input device '{}' was not found, using the default device instead",
+                    name
+                ));
+            }
+        }
+    }
+
+    /// Stop the current capture stream, if any, and start capturing from the selected
+    /// input device
+    pub fn restart_capture(&mut self) {
+        // Release the old device before opening the new one
+        self.capture_session = None;
+
+        if self.test_signal
+            && let Some(sender) = &self.sender
+        {
+            self.capture_session = Some(capture::spawn_test_signal_thread(
+                self.config.clone(),
+                sender.clone(),
+            ));
+            self.update_levels_wanted();
+            return;
+        }
+
+        let (Some(device), Some(input_config), Some(sender)) = (
+            &self.selected_input_device,
+            &self.selected_input_config,
+            &self.sender,
+        ) else {
+            return;
+        };
+
+        debug!("Starting capture from {} with {:?}", device, input_config);
+        self.capture_session = Some(capture::spawn_capture_thread(
+            self.config.clone(),
+            Arc::new(Mutex::new(device.raw_device.clone())),
+            Arc::new(Mutex::new(input_config.clone())),
+            sender.clone(),
+            self.notifier.clone(),
+        ));
+        self.update_levels_wanted();
+    }
+
+    /// Only the settings, setup, debug overlay and metrics show the levels while idle,
+    /// elsewhere the capture thread can stick to sending state changes
+    fn update_levels_wanted(&self) {
+        if let Some(capture_session) = &self.capture_session {
+            capture_session.set_levels_wanted(
+                matches!(self.curr_view, View::Settings | View::Setup(_))
+                    || self.debug_overlay
+                    || self.metrics.is_some(),
+            );
+        }
+    }
+
+    pub fn load_images(&mut self, path: &Path) {
+        let config = lock_and_unlock!(self.config).clone();
+        let options = ImageOptions::from(&config);
+
+        // The assets can be a folder of images or a bundle of them. A missing or
+        // unreadable one leaves no images, the home view then offers to pick another one
+        let (files, manifest) = if path.is_file() && has_extension(path, BUNDLE_EXTENSIONS) {
+            read_bundle(path, options).unwrap_or_else(|err| {
+                warn!("Failed to read avatar bundle {}: {}", path.display(), err);
+                Default::default()
+            })
+        } else {
+            read_assets_folder(path, options).unwrap_or_else(|err| {
+                warn!("Failed to read assets folder {}: {}", path.display(), err);
+                Default::default()
+            })
+        };
+
+        let mut images = vec![];
+        let mut named_images = HashMap::new();
+        let mut manifest_frames = HashMap::new();
+
+        // Images the manifest assigns to a state are used for it, several of them as an
+        // animation
+        for (state_name, frames) in &manifest.states {
+            let Some(state) = AvatarState::from_name(state_name) else {
+                warn!("Unknown avatar state '{}' in {}", state_name, MANIFEST_FILE);
+                continue;
+            };
+
+            let mut handles = frames
+                .files()
+                .iter()
+                .filter_map(|file| {
+                    let handle = files
+                        .iter()
+                        .find(|(file_name, _)| file_name == file)
+                        .map(|(_, handle)| handle.clone());
+
+                    if handle.is_none() {
+                        warn!("Image '{}' for state '{}' not found", file, state_name);
+                    }
+                    handle
+                })
+                .collect::<Vec<_>>();
+
+            match handles.len() {
+                0 => {}
+                1 => {
+                    named_images.insert(state, handles.remove(0));
+                }
+                _ => {
+                    manifest_frames.insert(state.name().to_string(), handles);
+                }
+            }
+        }
+
+        // Otherwise images named after a state (e.g. `idle.png`, `talk.png`) are used for
+        // that state, and any others in order of their file names
+        let in_manifest = manifest
+            .states
+            .values()
+            .flat_map(|frames| frames.files())
+            .collect::<HashSet<_>>();
+
+        for (file_name, image) in files {
+            if in_manifest.contains(&file_name) {
+                continue;
+            }
+
+            match AvatarState::from_file_name(Path::new(&file_name)) {
+                Some(state) => {
+                    named_images.entry(state).or_insert(image);
+                }
+                None => images.push(image),
+            }
+        }
+
+        self.set_images(images);
+        self.named_images = Arc::new(named_images);
+        self.avatar_frame_duration_ms = manifest.frame_duration_ms;
+        self.avatar_scale = manifest.scale;
+        self.closed_image = config.closed_image_index;
+        self.open_image = config.open_image_index;
+
+        // Folders of frames set in the config take precedence over the manifest's
+        self.load_state_frames(&config.state_dirs, options);
+        let state_frames = Arc::make_mut(&mut self.state_frames);
+        for (state, frames) in manifest_frames {
+            state_frames.entry(state).or_insert(frames);
+        }
+
+        self.splash_image = match config.splash_image {
+            Some(splash_path) if splash_path.is_file() => read_image(&splash_path, options)
+                .inspect_err(|err| {
+                    warn!(
+                        "Failed to load splash image {}: {}",
+                        splash_path.display(),
+                        err
+                    )
+                })
+                .ok(),
+            Some(splash_path) => {
+                warn!("Splash image {} not found", splash_path.display());
+                None
+            }
+            None => None,
+        };
+    }
+
+    /// Load the frames for each avatar state from its own folder, reporting
+    /// any state whose folder could not be used
+    pub fn load_state_frames(
+        &mut self,
+        state_dirs: &HashMap<String, PathBuf>,
+        options: ImageOptions,
+    ) {
+        let mut state_frames = HashMap::new();
+
+        for (state, dir) in state_dirs {
+            match read_frames(dir, options) {
+                Ok(frames) if frames.is_empty() => {
+                    warn!("No frames found for state '{}' in {}", state, dir.display());
+                }
+                Ok(frames) => {
+                    state_frames.insert(state.clone(), frames);
+                }
+                Err(err) => {
+                    warn!(
+                        "Failed to load frames for state '{}' from {}: {}",
+                        state,
+                        dir.display(),
+                        err
+                    );
+                }
+            }
+        }
+
+        self.state_frames = Arc::new(state_frames);
+    }
+
+    pub fn set_images(&mut self, images: Vec<Handle>) {
+        self.images = Arc::new(images);
+    }
+
+    pub fn get_image(&self, index: usize) -> Option<&Handle> {
+        self.images.get(index)
+    }
+
+    /// Image for a state, falling back to the image at `index` if the state has no
+    /// image of its own
+    pub fn state_image(&self, state: AvatarState, index: usize) -> Option<&Handle> {
+        self.state_frame(state).or_else(|| self.get_image(index))
+    }
+
+    /// Current animation frame of a state if it has a folder of frames, otherwise the
+    /// image named after it, if any
+    fn state_frame(&self, state: AvatarState) -> Option<&Handle> {
+        self.state_frames
+            .get(state.name())
+            .filter(|frames| !frames.is_empty())
+            .map(|frames| &frames[self.frame_index % frames.len()])
+            .or_else(|| self.named_images.get(&state))
+    }
+
+    /// Time spent talking this session and overall, including the current stretch
+    fn speaking_stats(&self) -> String {
+        let current = self
+            .talking_since
+            .map(|since| since.elapsed())
+            .unwrap_or_default();
+        let session = self.speaking_time + current;
+        let total =
+            Duration::from_millis(lock_and_unlock!(self.config).total_speaking_ms) + current;
+
+        format!(
+            "Talked for {} this session, {} in total",
+            format_duration(session),
+            format_duration(total)
+        )
+    }
+
+    fn mic_status(&self) -> String {
+        if self.test_signal {
+            format!("Test signal activated: {}", self.mic_activated)
+        } else if self.selected_input_device.is_none() {
+            "No microphone found, pick an input device in the settings".to_string()
+        } else if self.frozen_image.is_some() {
+            "Avatar frozen".to_string()
+        } else if self.muted {
+            "Microphone muted".to_string()
+        } else {
+            format!("Microphone activated: {}", self.mic_activated)
+        }
+    }
+
+    /// Image for the current talking state, swapped for a blink frame while blinking. A
+    /// frozen avatar always shows its frozen image
+    fn current_image(&self) -> Option<&Handle> {
+        if let Some(index) = self.frozen_image {
+            return self
+                .get_image(index)
+                .or_else(|| self.state_image(AvatarState::Idle, self.closed_image));
+        }
+
+        if self.blinking_until.is_some() {
+            let blink_state = match self.talking_tier {
+                Some(_) => AvatarState::TalkingBlink,
+                None => AvatarState::Blink,
+            };
+
+            if let Some(frame) = self.state_frame(blink_state) {
+                return Some(frame);
+            }
+        }
+
+        match self.talking_tier {
+            Some(tier) => self.talking_image(tier),
+            None => self.state_image(AvatarState::Idle, self.closed_image),
+        }
+    }
+
+    /// Image for the given loudness tier while talking, where tier 0 is regular talking
+    /// and each tier above it uses the next image. The loud talking image is used for the
+    /// first tier, and for any tier without an image of its own. Falls back to the
+    /// talking image if there aren't enough images for the tier
+    pub fn talking_image(&self, tier: usize) -> Option<&Handle> {
+        if tier == 0 {
+            return self.state_image(AvatarState::Talking, self.open_image);
+        }
+
+        let loud = self.state_frame(AvatarState::TalkingLoud);
+        let indexed = self.get_image(self.open_image + tier);
+        let image = if tier == 1 {
+            loud.or(indexed)
+        } else {
+            indexed.or(loud)
+        };
+
+        image.or_else(|| self.state_image(AvatarState::Talking, self.open_image))
+    }
+}
+
+/// Read the images in the assets folder, along with their file names in order
+fn read_assets_folder(
+    dir: &Path,
+    options: ImageOptions,
+) -> io::Result<(Vec<(String, Handle)>, AvatarManifest)> {
+    let mut paths = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| is_image_file(path))
+        .collect::<Vec<_>>();
+    paths.sort();
+
+    let files = paths
+        .into_iter()
+        .filter_map(|path| {
+            let file_name = path.file_name()?.to_string_lossy().into_owned();
+
+            read_image(&path, options)
+                .inspect_err(|err| warn!("Skipping image {}: {}", path.display(), err))
+                .ok()
+                .map(|image| (file_name, image))
+        })
+        .collect();
+
+    // The manifest is optional, without one the file name conventions apply
+    let manifest_path = dir.join(MANIFEST_FILE);
+    let manifest = match std::fs::read_to_string(&manifest_path) {
+        Ok(contents) => AvatarManifest::parse(&contents, &manifest_path),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => AvatarManifest::default(),
+        Err(err) => {
+            warn!("Failed to read {}: {}", manifest_path.display(), err);
+            AvatarManifest::default()
+        }
+    };
+
+    Ok((files, manifest))
+}
+
+/// Read the images and manifest of an avatar bundle, unpacking it in memory. Images are
+/// returned along with their file names, in order
+fn read_bundle(
+    path: &Path,
+    options: ImageOptions,
+) -> io::Result<(Vec<(String, Handle)>, AvatarManifest)> {
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(path)?).map_err(io::Error::other)?;
+
+    let mut files = vec![];
+    let mut manifest = AvatarManifest::default();
+
+    for index in 0..archive.len() {
+        let mut entry = archive.by_index(index).map_err(io::Error::other)?;
+        if !entry.is_file() {
+            continue;
+        }
+
+        // Only the file name matters, so bundles can keep their images in a folder
+        let Some(file_name) = Path::new(entry.name())
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+        else {
+            continue;
+        };
+
+        let mut contents = vec![];
+        io::Read::read_to_end(&mut entry, &mut contents)?;
+
+        if file_name == MANIFEST_FILE {
+            manifest = AvatarManifest::parse(&String::from_utf8_lossy(&contents), path);
+        } else if has_extension(Path::new(&file_name), IMAGE_EXTENSIONS) {
+            match ::image::load_from_memory(&contents) {
+                Ok(image) => files.push((file_name, to_handle(image.into_rgba8(), options))),
+                Err(err) => warn!("Skipping image {} in bundle: {}", file_name, err),
+            }
+        }
+    }
+
+    files.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+    Ok((files, manifest))
+}
+
+/// Read every file in a folder as a frame, ordered by file name
+fn read_frames(dir: &Path, options: ImageOptions) -> io::Result<Vec<Handle>> {
+    let mut paths = std::fs::read_dir(dir)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<io::Result<Vec<PathBuf>>>()?;
+
+    paths.retain(|path| is_image_file(path) || has_extension(path, &["gif"]));
+    paths.sort();
+
+    let mut frames = vec![];
+    for path in paths {
+        // Animated GIFs are expanded into one frame per GIF frame
+        let result = if has_extension(&path, &["gif"]) {
+            read_gif_frames(&path, options)
+        } else {
+            read_image(&path, options).map(|frame| vec![frame])
+        };
+
+        match result {
+            Ok(frame) => frames.extend(frame),
+            Err(err) => warn!("Skipping frame {}: {}", path.display(), err),
+        }
+    }
+
+    Ok(frames)
+}
+
+/// Whether a path is a file in one of the supported still image formats
+fn is_image_file(path: &Path) -> bool {
+    path.is_file() && has_extension(path, IMAGE_EXTENSIONS)
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension().is_some_and(|ext| {
+        extensions
+            .iter()
+            .any(|extension| ext.eq_ignore_ascii_case(extension))
+    })
+}
+
+/// Decode an image up front, so broken files are reported when loading rather than
+/// showing up blank
+fn read_image(path: &Path, options: ImageOptions) -> io::Result<Handle> {
+    let image = ::image::open(path).map_err(io::Error::other)?.into_rgba8();
+
+    Ok(to_handle(image, options))
+}
+
+/// Turn decoded pixels into an image handle, flipping them first if mirrored and
+/// hardening the edges if matted
+fn to_handle(mut image: RgbaImage, options: ImageOptions) -> Handle {
+    if options.mirror {
+        ::image::imageops::flip_horizontal_in_place(&mut image);
+    }
+
+    // Partly transparent edge pixels blend with the chroma key color, leaving a fringe
+    // of it around the avatar after keying. Making them either opaque or transparent
+    // leaves nothing to blend
+    if let Some(threshold) = options.matte_threshold {
+        // Fully transparent pixels always stay transparent
+        let threshold = ((threshold * 255.0) as u8).max(1);
+
+        for pixel in image.pixels_mut() {
+            pixel[3] = if pixel[3] >= threshold { 255 } else { 0 };
+        }
+    }
+
+    let (width, height) = image.dimensions();
+    Handle::from_rgba(width, height, image.into_raw())
+}
+
+/// Decode every frame of an animated GIF
+fn read_gif_frames(path: &Path, options: ImageOptions) -> io::Result<Vec<Handle>> {
+    let file = io::BufReader::new(std::fs::File::open(path)?);
+    let decoder = GifDecoder::new(file).map_err(io::Error::other)?;
+
+    decoder
+        .into_frames()
+        .map(|frame| {
+            let buffer = frame.map_err(io::Error::other)?.into_buffer();
+
+            Ok(to_handle(buffer, options))
+        })
+        .collect()
 }
@@ -1,30 +1,64 @@
+#[cfg(target_os = "linux")]
 use std::collections::HashMap;
+#[cfg(target_os = "linux")]
 use std::ffi::CString;
+#[cfg(target_os = "linux")]
+use std::sync::Mutex;
+#[cfg(target_os = "linux")]
+use std::time::{Duration, Instant};
 
 #[cfg(target_os = "linux")]
-pub fn get_alsa_hint_for(name: &str) -> Option<String> {
-    let hints = get_alsa_hints();
-    hints.get(name).cloned()
-}
+use log::warn;
+
+/// How long hints are reused for. Long enough to cover listing the devices and finding the
+/// default one, short enough that refreshing picks up newly plugged in devices
+#[cfg(target_os = "linux")]
+const HINTS_CACHE_DURATION: Duration = Duration::from_secs(2);
 
+#[cfg(target_os = "linux")]
+static HINTS_CACHE: Mutex<Option<(Instant, HashMap<String, String>)>> = Mutex::new(None);
+
+/// Descriptions of the ALSA capture devices by name, reusing recent results
 #[cfg(target_os = "linux")]
 pub fn get_alsa_hints() -> HashMap<String, String> {
+    let mut cache = HINTS_CACHE.lock().unwrap();
+
+    if let Some((queried, hints)) = &*cache
+        && queried.elapsed() < HINTS_CACHE_DURATION
+    {
+        return hints.clone();
+    }
+
+    let hints = query_alsa_hints();
+    *cache = Some((Instant::now(), hints.clone()));
+    hints
+}
+
+#[cfg(target_os = "linux")]
+fn query_alsa_hints() -> HashMap<String, String> {
     use alsa::Direction;
     use alsa::device_name::HintIter;
 
     let mut hints = HashMap::new();
 
     let iface = CString::new("pcm").unwrap();
-    let hint_iter = HintIter::new(None, &iface).expect("Failed to get ALSA hints");
+    let hint_iter = match HintIter::new(None, &iface) {
+        Ok(hint_iter) => hint_iter,
+        Err(err) => {
+            warn!("Failed to get ALSA hints: {}", err);
+            return hints;
+        }
+    };
 
     for hint in hint_iter {
-        let name = hint.name.expect("Failed to get hint name");
-        let desc = hint.desc.expect("Failed to get hint description");
+        let (Some(name), Some(desc)) = (hint.name, hint.desc) else {
+            continue;
+        };
 
-        if let Some(direction) = hint.direction {
-            if direction != Direction::Capture {
-                continue;
-            }
+        if let Some(direction) = hint.direction
+            && direction != Direction::Capture
+        {
+            continue;
         }
 
         hints.insert(name.to_string(), desc.to_string());
@@ -32,3 +66,169 @@ pub fn get_alsa_hints() -> HashMap<String, String> {
 
     hints
 }
+
+/// Readable name for an ALSA device, e.g. "hw:CARD=PCH,DEV=0" becomes "PCH (hardware,
+/// device 0)" unless ALSA has a description for it
+#[cfg(target_os = "linux")]
+pub fn friendly_name(dev_name: &str, hints: &HashMap<String, String>) -> String {
+    let lower = dev_name.to_lowercase();
+
+    // Sound servers, which route audio from whichever device is configured in them
+    if lower.contains("pipewire") {
+        return "Pipewire Media Server".to_string();
+    } else if lower.contains("pulse") {
+        return "PulseAudio".to_string();
+    } else if lower.contains("jack") {
+        return "JACK Audio Connection Kit".to_string();
+    }
+
+    match lower.as_str() {
+        "default" => return "Default device".to_string(),
+        "sysdefault" => return "System default device".to_string(),
+        _ => {}
+    }
+
+    // Descriptions span several lines, the first one names the card and device
+    if let Some(desc) = hints.get(dev_name).and_then(|desc| desc.lines().next()) {
+        return desc.to_string();
+    }
+
+    // Otherwise build a name from the plugin and the card, e.g. "hw:CARD=PCH,DEV=0"
+    let Some((plugin, params)) = dev_name.split_once(':') else {
+        return dev_name.to_string();
+    };
+
+    let param = |key: &str| {
+        params
+            .split(',')
+            .find_map(|param| param.strip_prefix(key)?.strip_prefix('='))
+    };
+
+    let Some(card) = param("CARD") else {
+        return dev_name.to_string();
+    };
+
+    let kind = match plugin {
+        "hw" => "hardware",
+        "plughw" => "hardware with conversion",
+        "sysdefault" => "system default",
+        "dsnoop" => "shared",
+        other => other,
+    };
+
+    match param("DEV") {
+        Some(dev) => format!("{} ({}, device {})", card, kind, dev),
+        None => format!("{} ({})", card, kind),
+    }
+}
+
+/// Run `f` with ALSA's error messages sent to the debug log instead of stderr
+///
+/// Listing devices makes ALSA print messages like "unable to open slave" for every plugin
+/// that can't be opened, which look like real errors but are harmless. They can still be
+/// seen with `RUST_LOG=chibi=debug`
+#[cfg(target_os = "linux")]
+pub fn with_alsa_errors_logged<T>(f: impl FnOnce() -> T) -> T {
+    use std::ffi::{CStr, c_char, c_int};
+
+    // Serializes swapping the handler, so overlapping calls (e.g. the UI and the capture
+    // thread both listing devices) each restore the handler they replaced in order
+    static HANDLER_LOCK: Mutex<()> = Mutex::new(());
+
+    // The local handler takes the printf arguments as a `va_list` rather than variadic
+    // arguments, so it can be written in Rust with the exact signature ALSA calls it with
+    unsafe extern "C" fn log_alsa_error(
+        file: *const c_char,
+        line: c_int,
+        function: *const c_char,
+        err: c_int,
+        fmt: *const c_char,
+        _args: *mut alsa_sys::__va_list_tag,
+    ) {
+        let text = |ptr: *const c_char| {
+            if ptr.is_null() {
+                String::new()
+            } else {
+                unsafe { CStr::from_ptr(ptr) }
+                    .to_string_lossy()
+                    .into_owned()
+            }
+        };
+
+        // The printf arguments can't be read here, so the message is logged unformatted
+        log::debug!(
+            "ALSA {}:{} ({}) error {}: {}",
+            text(file),
+            line,
+            text(function),
+            err,
+            text(fmt)
+        );
+    }
+
+    let _guard = HANDLER_LOCK.lock().unwrap_or_else(|err| err.into_inner());
+
+    // The local handler only applies to the calling thread, and takes precedence over
+    // ALSA's default handler, which would print to stderr
+    let previous = unsafe { alsa_sys::snd_lib_error_set_local(Some(log_alsa_error)) };
+    let result = f();
+    unsafe { alsa_sys::snd_lib_error_set_local(previous) };
+
+    result
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn with_alsa_errors_logged<T>(f: impl FnOnce() -> T) -> T {
+    f()
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_sound_servers() {
+        let hints = HashMap::new();
+
+        assert_eq!(friendly_name("pipewire", &hints), "Pipewire Media Server");
+        assert_eq!(friendly_name("pulse", &hints), "PulseAudio");
+        assert_eq!(friendly_name("jack", &hints), "JACK Audio Connection Kit");
+        assert_eq!(friendly_name("default", &hints), "Default device");
+    }
+
+    #[test]
+    fn prefers_first_line_of_description() {
+        let hints = HashMap::from([(
+            "hw:CARD=PCH,DEV=0".to_string(),
+            "HDA Intel PCH, ALC892 Analog\nDirect hardware device".to_string(),
+        )]);
+
+        assert_eq!(
+            friendly_name("hw:CARD=PCH,DEV=0", &hints),
+            "HDA Intel PCH, ALC892 Analog"
+        );
+    }
+
+    #[test]
+    fn builds_name_from_plugin_and_card() {
+        let hints = HashMap::new();
+
+        assert_eq!(
+            friendly_name("hw:CARD=PCH,DEV=0", &hints),
+            "PCH (hardware, device 0)"
+        );
+        assert_eq!(
+            friendly_name("dsnoop:CARD=Mic,DEV=1", &hints),
+            "Mic (shared, device 1)"
+        );
+        assert_eq!(friendly_name("front:CARD=PCH", &hints), "PCH (front)");
+    }
+
+    #[test]
+    fn keeps_names_it_cant_parse() {
+        let hints = HashMap::new();
+
+        assert_eq!(friendly_name("null", &hints), "null");
+        assert_eq!(friendly_name("hw:0,0", &hints), "hw:0,0");
+    }
+}
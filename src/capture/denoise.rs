@@ -0,0 +1,45 @@
+use nnnoiseless::DenoiseState;
+
+// RNNoise works on samples in the range of an i16
+const SAMPLE_SCALE: f32 = 32767.0;
+
+/// RNNoise based noise suppression
+///
+/// RNNoise expects 48kHz mono audio in frames of 480 samples, so incoming audio is
+/// downmixed and buffered until a full frame is available
+pub struct Denoiser {
+    state: Box<DenoiseState<'static>>,
+    pending: Vec<f32>,
+    frame: Vec<f32>,
+}
+
+impl Denoiser {
+    pub fn new() -> Self {
+        Self {
+            state: DenoiseState::new(),
+            pending: Vec::with_capacity(DenoiseState::FRAME_SIZE * 2),
+            frame: vec![0.0; DenoiseState::FRAME_SIZE],
+        }
+    }
+
+    /// Denoise interleaved samples, appending the denoised mono samples of every
+    /// completed frame to `output`
+    pub fn process(&mut self, data: &[f32], channels: usize, output: &mut Vec<f32>) {
+        for frame in data.chunks(channels) {
+            let mono = frame.iter().sum::<f32>() / frame.len() as f32;
+            self.pending.push(mono * SAMPLE_SCALE);
+        }
+
+        while self.pending.len() >= DenoiseState::FRAME_SIZE {
+            self.state
+                .process_frame(&mut self.frame, &self.pending[..DenoiseState::FRAME_SIZE]);
+            self.pending.drain(..DenoiseState::FRAME_SIZE);
+
+            output.extend(
+                self.frame
+                    .iter()
+                    .map(|&sample| (sample / SAMPLE_SCALE).clamp(-1.0, 1.0)),
+            );
+        }
+    }
+}
@@ -0,0 +1,98 @@
+use std::sync::{Arc, Mutex};
+
+use async_channel::{Receiver, Sender};
+use cpal::{DefaultStreamConfigError, SupportedStreamConfig};
+
+use crate::capture::{
+    CaptureSession, InputDevice, MicEvent, get_stream_config, spawn_capture_thread,
+};
+use crate::config::ChibiConfig;
+
+/// Events queued for the embedder, about three seconds of level updates. Events are
+/// dropped while the queue is full, so a detector nobody reads from doesn't grow forever
+const EVENT_QUEUE_LEN: usize = 64;
+
+/// Microphone activity detection without the GUI, for embedding chibi in other programs
+///
+/// Events are received from `events()`, which is also a `Stream` for async code:
+///
+/// ```no_run
+/// use chibi::capture::{MicDetector, get_default_device};
+/// use chibi::config::ChibiConfig;
+///
+/// let device = get_default_device(None).expect("No input device");
+/// let mut detector = MicDetector::new(ChibiConfig::default(), device).unwrap();
+/// detector.start();
+///
+/// while let Ok(event) = detector.events().recv_blocking() {
+///     println!("Talking: {}", event.active);
+/// }
+/// ```
+pub struct MicDetector {
+    config: Arc<Mutex<ChibiConfig>>,
+    device: InputDevice,
+    stream_config: SupportedStreamConfig,
+
+    sender: Sender<MicEvent>,
+    receiver: Receiver<MicEvent>,
+    session: Option<CaptureSession>,
+}
+
+impl MicDetector {
+    /// Create a detector for a device, using the input format from the config if the
+    /// device supports it
+    pub fn new(config: ChibiConfig, device: InputDevice) -> Result<Self, DefaultStreamConfigError> {
+        let stream_config = get_stream_config(&device.raw_device, config.input_format)?;
+        let (sender, receiver) = async_channel::bounded(EVENT_QUEUE_LEN);
+
+        Ok(Self {
+            config: Arc::new(Mutex::new(config)),
+            device,
+            stream_config,
+            sender,
+            receiver,
+            session: None,
+        })
+    }
+
+    /// Start capturing, restarting if already running
+    pub fn start(&mut self) {
+        // Release the device before opening it again
+        self.session = None;
+
+        let session = spawn_capture_thread(
+            self.config.clone(),
+            Arc::new(Mutex::new(self.device.raw_device.clone())),
+            Arc::new(Mutex::new(self.stream_config.clone())),
+            self.sender.clone(),
+            None,
+        );
+
+        // Without a UI showing meters, levels are still useful to embedders
+        session.set_levels_wanted(true);
+        self.session = Some(session);
+    }
+
+    /// Stop capturing and release the device
+    pub fn stop(&mut self) {
+        self.session = None;
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.session.is_some()
+    }
+
+    /// Detection events, sent on state changes and regularly with the current level
+    pub fn events(&self) -> Receiver<MicEvent> {
+        self.receiver.clone()
+    }
+
+    /// Shared config, changes to the detection settings apply while running
+    pub fn config(&self) -> Arc<Mutex<ChibiConfig>> {
+        self.config.clone()
+    }
+
+    pub fn device(&self) -> &InputDevice {
+        &self.device
+    }
+}
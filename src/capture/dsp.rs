@@ -0,0 +1,106 @@
+use std::collections::VecDeque;
+
+/// Level reported for silence, since the logarithm of zero is undefined
+pub const SILENCE_DB: f32 = -100.0;
+
+/// Convert an amplitude to decibels relative to full scale (dBFS)
+pub fn amplitude_to_db(amplitude: f32) -> f32 {
+    if amplitude <= 0.0 {
+        return SILENCE_DB;
+    }
+
+    (20.0 * amplitude.log10()).max(SILENCE_DB)
+}
+
+/// Convert decibels relative to full scale (dBFS) back to an amplitude
+pub fn db_to_amplitude(db: f32) -> f32 {
+    10.0_f32.powf(db / 20.0)
+}
+
+/// Root mean square (RMS) amplitude of a signal
+///
+/// Silence is 0.0, a full scale square wave 1.0 and a full scale sine wave about 0.707
+/// (1/√2). An empty signal has no level, so it is treated as silence
+pub fn rms_amplitude(samples: &[f32]) -> f32 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+
+    let sum: f32 = samples.iter().map(|x| x * x).sum();
+    (sum / samples.len() as f32).sqrt()
+}
+
+/// Peak amplitude of a signal, the largest absolute sample
+///
+/// Reacts to sharp transients like consonants straight away, where the RMS amplitude
+/// averages them out. An empty signal is treated as silence
+pub fn peak_amplitude(samples: &[f32]) -> f32 {
+    samples.iter().fold(0.0, |peak, x| x.abs().max(peak))
+}
+
+/// Take a single channel out of interleaved samples, appending it to `output`
+pub fn extract_channel(samples: &[f32], channels: usize, channel: usize, output: &mut Vec<f32>) {
+    let channels = channels.max(1);
+
+    output.extend(
+        samples
+            .iter()
+            .skip(channel.min(channels - 1))
+            .step_by(channels),
+    );
+}
+
+/// Moving average over the most recent values
+#[derive(Default)]
+pub struct MovingAverage {
+    values: VecDeque<f32>,
+}
+
+impl MovingAverage {
+    /// Add a value, returning the average of the last `window` values
+    pub fn push(&mut self, value: f32, window: usize) -> f32 {
+        self.values.push_back(value);
+
+        while self.values.len() > window.max(1) {
+            self.values.pop_front();
+        }
+
+        self.values.iter().sum::<f32>() / self.values.len() as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moving_average_of_partial_window() {
+        let mut average = MovingAverage::default();
+
+        assert_eq!(average.push(3.0, 3), 3.0);
+        assert_eq!(average.push(1.0, 3), 2.0);
+    }
+
+    #[test]
+    fn moving_average_drops_oldest_values() {
+        let mut average = MovingAverage::default();
+
+        for value in [10.0, 1.0, 2.0] {
+            average.push(value, 2);
+        }
+        assert_eq!(average.push(4.0, 2), 3.0);
+    }
+
+    #[test]
+    fn moving_average_follows_window_changes() {
+        let mut average = MovingAverage::default();
+
+        for value in [1.0, 2.0, 3.0, 4.0] {
+            average.push(value, 4);
+        }
+
+        // A window of 0 is treated as 1, just the latest value
+        assert_eq!(average.push(8.0, 2), 6.0);
+        assert_eq!(average.push(5.0, 0), 5.0);
+    }
+}
@@ -0,0 +1,94 @@
+use std::f32::consts::{FRAC_1_SQRT_2, TAU};
+
+// Frequency range of the human voice that is kept by the voice filter
+const VOICE_LOW_HZ: f32 = 100.0;
+const VOICE_HIGH_HZ: f32 = 4000.0;
+
+/// Second order IIR filter, using the coefficients from the Audio EQ Cookbook
+#[derive(Clone, Copy)]
+struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+}
+
+impl Biquad {
+    fn high_pass(cutoff_hz: f32, sample_rate: f32) -> Self {
+        let (cos, alpha) = Self::cutoff_terms(cutoff_hz, sample_rate);
+        Self::normalized(
+            [(1.0 + cos) / 2.0, -(1.0 + cos), (1.0 + cos) / 2.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    }
+
+    fn low_pass(cutoff_hz: f32, sample_rate: f32) -> Self {
+        let (cos, alpha) = Self::cutoff_terms(cutoff_hz, sample_rate);
+        Self::normalized(
+            [(1.0 - cos) / 2.0, 1.0 - cos, (1.0 - cos) / 2.0],
+            [1.0 + alpha, -2.0 * cos, 1.0 - alpha],
+        )
+    }
+
+    fn cutoff_terms(cutoff_hz: f32, sample_rate: f32) -> (f32, f32) {
+        // Keep the cutoff below Nyquist, otherwise the filter becomes unstable
+        let w0 = TAU * cutoff_hz.min(sample_rate * 0.45) / sample_rate;
+        (w0.cos(), w0.sin() / (2.0 * FRAC_1_SQRT_2))
+    }
+
+    fn normalized(b: [f32; 3], a: [f32; 3]) -> Self {
+        Self {
+            b0: b[0] / a[0],
+            b1: b[1] / a[0],
+            b2: b[2] / a[0],
+            a1: a[1] / a[0],
+            a2: a[2] / a[0],
+        }
+    }
+
+    /// Filter a single sample, using the transposed direct form II
+    fn process(&self, sample: f32, state: &mut [f32; 2]) -> f32 {
+        let output = self.b0 * sample + state[0];
+        state[0] = self.b1 * sample - self.a1 * output + state[1];
+        state[1] = self.b2 * sample - self.a2 * output;
+        output
+    }
+}
+
+/// Band-pass filter keeping the frequencies of the human voice, so low rumble (fans,
+/// desk thumps) and high hiss don't count towards the level
+pub struct VoiceFilter {
+    high_pass: Biquad,
+    low_pass: Biquad,
+
+    // Filter state for each channel
+    state: Vec<[[f32; 2]; 2]>,
+}
+
+impl VoiceFilter {
+    pub fn new(sample_rate: u32) -> Self {
+        let sample_rate = sample_rate as f32;
+
+        Self {
+            high_pass: Biquad::high_pass(VOICE_LOW_HZ, sample_rate),
+            low_pass: Biquad::low_pass(VOICE_HIGH_HZ, sample_rate),
+            state: vec![],
+        }
+    }
+
+    /// Filter interleaved samples in place
+    pub fn process(&mut self, data: &mut [f32], channels: usize) {
+        let channels = channels.max(1);
+        if self.state.len() != channels {
+            self.state = vec![[[0.0; 2]; 2]; channels];
+        }
+
+        for frame in data.chunks_mut(channels) {
+            for (sample, state) in frame.iter_mut().zip(self.state.iter_mut()) {
+                let high_passed = self.high_pass.process(*sample, &mut state[0]);
+                *sample = self.low_pass.process(high_passed, &mut state[1]);
+            }
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use crate::capture::DetectionSettings;
+use rand::Rng;
+
+/// Noise gate with hysteresis (aka "deadband")
+///
+/// The gate only opens once the signal has stayed above the threshold for the attack time
+/// and the minimum open time, so one-off spikes never open it, and only closes once it has
+/// stayed below the off-threshold for the release time. It is also held open for the hold
+/// time after the signal was last above the threshold, so short pauses between words don't
+/// close it
+#[derive(Default)]
+pub struct NoiseGate {
+    open: bool,
+
+    // Time the signal has spent past the threshold that would flip the gate, counted from
+    // the duration of the audio blocks
+    pending_ms: f32,
+
+    // Time since the signal was last above the threshold
+    since_above_ms: f32,
+}
+
+impl NoiseGate {
+    /// Feed the level (in dB) of a block of audio lasting `elapsed_ms` through the gate,
+    /// returning whether the gate is open afterwards
+    pub fn process(
+        &mut self,
+        level_db: f32,
+        elapsed_ms: f32,
+        settings: &DetectionSettings,
+    ) -> bool {
+        let threshold_on_db = settings.threshold_db;
+        let threshold_off_db = settings.threshold_off_db;
+
+        let (crossing, required_ms) = if self.open {
+            (level_db < threshold_off_db, settings.release_ms)
+        } else {
+            let required_ms = settings.attack_ms.max(settings.min_open_ms);
+            (level_db >= threshold_on_db, required_ms)
+        };
+
+        if level_db >= threshold_on_db {
+            self.since_above_ms = 0.0;
+        } else {
+            self.since_above_ms += elapsed_ms;
+        }
+
+        if crossing {
+            self.pending_ms += elapsed_ms;
+
+            let held = self.open && self.since_above_ms < settings.hold_time_ms as f32;
+
+            if self.pending_ms >= required_ms as f32 && !held {
+                self.open = !self.open;
+                self.pending_ms = 0.0;
+            }
+        } else {
+            self.pending_ms = 0.0;
+        }
+
+        self.open
+    }
+}
+
+/// Flickers the gate output on and off at random intervals while the gate is open
+///
+/// Timing is driven by the duration of the audio blocks, so the audio callback never has
+/// to sleep
+#[derive(Default)]
+pub struct Flicker {
+    shown: bool,
+
+    // Time left before the flicker flips
+    remaining_ms: f32,
+}
+
+impl Flicker {
+    /// Advance the flicker by a block of audio lasting `elapsed_ms`, returning whether
+    /// the microphone should be shown as active
+    pub fn process(&mut self, open: bool, elapsed_ms: f32, settings: &DetectionSettings) -> bool {
+        if !open {
+            *self = Self::default();
+            return false;
+        }
+
+        self.remaining_ms -= elapsed_ms;
+
+        if self.remaining_ms <= 0.0 {
+            // Pick a random duration for the flicker to make it look more natural
+            self.shown = !self.shown;
+            let min_ms = settings.flicker_min_ms;
+            let max_ms = settings.flicker_max_ms.max(min_ms);
+            self.remaining_ms = rand::rng().random_range(min_ms..=max_ms) as f32;
+        }
+
+        self.shown
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ChibiConfig, DeadbandMode};
+
+    const BLOCK_MS: f32 = 10.0;
+    const LOUD_DB: f32 = -10.0;
+    const DEADBAND_DB: f32 = -24.0;
+    const QUIET_DB: f32 = -50.0;
+
+    /// Settings with the gate opening at -18 dB and closing below -30 dB
+    fn settings(configure: impl FnOnce(&mut ChibiConfig)) -> DetectionSettings {
+        let mut config = ChibiConfig::default();
+        config.microphone_threshold_db = -18.0;
+        config.deadband_mode = DeadbandMode::Absolute;
+        config.off_threshold_db = -30.0;
+        configure(&mut config);
+        DetectionSettings::from(&config)
+    }
+
+    /// Feed `duration_ms` of audio at a constant level through the gate in 10 ms blocks
+    fn feed(
+        gate: &mut NoiseGate,
+        level_db: f32,
+        duration_ms: f32,
+        settings: &DetectionSettings,
+    ) -> bool {
+        let mut open = gate.open;
+        for _ in 0..(duration_ms / BLOCK_MS) as usize {
+            open = gate.process(level_db, BLOCK_MS, settings);
+        }
+        open
+    }
+
+    #[test]
+    fn opens_after_attack_time() {
+        let settings = settings(|config| config.attack_ms = 30);
+        let mut gate = NoiseGate::default();
+
+        assert!(!feed(&mut gate, LOUD_DB, 20.0, &settings));
+        assert!(feed(&mut gate, LOUD_DB, 10.0, &settings));
+    }
+
+    #[test]
+    fn bursts_shorter_than_attack_time_dont_open() {
+        let settings = settings(|config| config.attack_ms = 30);
+        let mut gate = NoiseGate::default();
+
+        assert!(!feed(&mut gate, LOUD_DB, 20.0, &settings));
+        assert!(!feed(&mut gate, QUIET_DB, 10.0, &settings));
+        assert!(!feed(&mut gate, LOUD_DB, 20.0, &settings));
+    }
+
+    #[test]
+    fn closes_after_release_time() {
+        let settings = settings(|config| config.release_ms = 100);
+        let mut gate = NoiseGate::default();
+
+        assert!(feed(&mut gate, LOUD_DB, 10.0, &settings));
+        assert!(feed(&mut gate, QUIET_DB, 90.0, &settings));
+        assert!(!feed(&mut gate, QUIET_DB, 10.0, &settings));
+    }
+
+    #[test]
+    fn stays_open_within_deadband() {
+        let settings = settings(|_| {});
+        let mut gate = NoiseGate::default();
+
+        assert!(feed(&mut gate, LOUD_DB, 10.0, &settings));
+        assert!(feed(&mut gate, DEADBAND_DB, 1000.0, &settings));
+    }
+
+    #[test]
+    fn hold_keeps_gate_open_through_short_pauses() {
+        let settings = settings(|config| {
+            config.release_ms = 0;
+            config.hold_time_ms = 200;
+        });
+        let mut gate = NoiseGate::default();
+
+        assert!(feed(&mut gate, LOUD_DB, 10.0, &settings));
+        assert!(feed(&mut gate, QUIET_DB, 190.0, &settings));
+        assert!(!feed(&mut gate, QUIET_DB, 10.0, &settings));
+    }
+
+    #[test]
+    fn spikes_shorter_than_min_open_time_dont_open() {
+        let settings = settings(|config| config.min_open_ms = 50);
+        let mut gate = NoiseGate::default();
+
+        // The attack time alone would have opened the gate on the first block
+        assert!(!feed(&mut gate, LOUD_DB, 40.0, &settings));
+        assert!(!feed(&mut gate, QUIET_DB, 100.0, &settings));
+
+        assert!(!feed(&mut gate, LOUD_DB, 40.0, &settings));
+        assert!(feed(&mut gate, LOUD_DB, 10.0, &settings));
+    }
+
+    #[test]
+    fn flicker_is_hidden_while_gate_is_closed() {
+        let settings = settings(|_| {});
+        let mut flicker = Flicker::default();
+
+        assert!(flicker.process(true, BLOCK_MS, &settings));
+        assert!(!flicker.process(false, BLOCK_MS, &settings));
+
+        // Reopening starts over from shown
+        assert!(flicker.process(true, BLOCK_MS, &settings));
+    }
+
+    #[test]
+    fn flicker_flips_after_interval() {
+        let settings = settings(|config| {
+            config.flicker_min_ms = 30;
+            config.flicker_max_ms = 30;
+        });
+        let mut flicker = Flicker::default();
+
+        let shown: Vec<bool> = (0..7)
+            .map(|_| flicker.process(true, BLOCK_MS, &settings))
+            .collect();
+        assert_eq!(shown, [true, true, true, false, false, false, true]);
+    }
+}
@@ -1,17 +1,43 @@
 mod alsa_util;
+#[cfg(feature = "rnnoise")]
+mod denoise;
+mod detector;
+pub mod dsp;
+mod filter;
+mod gate;
+mod monitor;
+mod osc;
+mod recorder;
+mod resample;
+mod test_signal;
 
 use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc;
 use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
 use std::time::Duration;
 
-use crate::config::ChibiConfig;
+use crate::capture::dsp::{
+    MovingAverage, amplitude_to_db, extract_channel, peak_amplitude, rms_amplitude,
+};
+use crate::capture::filter::VoiceFilter;
+use crate::capture::gate::{Flicker, NoiseGate};
+use crate::capture::monitor::{Monitor, MonitorSink};
+use crate::capture::osc::OscSender;
+use crate::capture::recorder::Recorder;
+use crate::capture::resample::{INTERNAL_SAMPLE_RATE, Resampler};
+use crate::capture::test_signal::TestSignal;
+use crate::config::{ChannelMode, ChibiConfig, DetectionMode, StreamFormat};
 use crate::lock_and_unlock;
 use async_channel::Sender;
 use cpal::{
-    Device, SupportedStreamConfig,
+    DefaultStreamConfigError, Device, SampleFormat, SampleRate, SupportedStreamConfig,
     traits::{DeviceTrait, HostTrait, StreamTrait},
 };
-use rand::Rng;
+use log::{debug, info, warn};
+
+pub use detector::MicDetector;
 
 /// Abstraction over `cpal::Device` which includes a friendly name
 #[derive(Clone)]
@@ -32,6 +58,16 @@ impl fmt::Display for InputDevice {
     }
 }
 
+// `cpal::Device` can't be compared, so devices are compared on their names instead
+impl PartialEq for InputDevice {
+    fn eq(&self, other: &Self) -> bool {
+        match (self.raw_device.name(), other.raw_device.name()) {
+            (Ok(name), Ok(other_name)) => name == other_name,
+            _ => self.friendly_name == other.friendly_name,
+        }
+    }
+}
+
 impl InputDevice {
     pub fn new(raw_device: cpal::Device, friendly_name: String) -> Self {
         Self {
@@ -41,82 +77,258 @@ impl InputDevice {
     }
 }
 
-/// Root mean square (RMS) amplitude of a signal
-fn rms_amplitude(samples: &[f32]) -> f32 {
-    let sum: f32 = samples.iter().map(|x| x * x).sum();
-    (sum / samples.len() as f32).sqrt()
-}
+/// How often level updates are sent while the state stays the same. Every event makes
+/// the UI redraw, so sending one per audio callback would keep it busy even when idle
+const LEVEL_UPDATE_INTERVAL_MS: f32 = 50.0;
 
-/// Wrapper over `cpal::default_input_device`
-pub fn get_default_device() -> Option<InputDevice> {
-    let host = cpal::default_host();
-    let default_device = host
-        .default_input_device()
-        .expect("Failed to get default input device");
+/// How far above the threshold the level has to be for full talking intensity, in dB
+const INTENSITY_RANGE_DB: f32 = 20.0;
 
-    let input_device;
+/// Detection result sent from the capture thread to the UI
+#[derive(Debug, Clone, Copy, Default)]
+pub struct MicEvent {
+    /// Whether the microphone is considered active
+    pub active: bool,
 
-    // Query it with ALSA hints on Linux
-    #[cfg(target_os = "linux")]
-    {
-        use crate::capture::alsa_util;
+    /// RMS amplitude of the signal as received from the device
+    pub raw_rms: f32,
+
+    /// Level of the signal after processing, compared against the threshold. The RMS
+    /// amplitude, or the peak amplitude in the peak detection mode
+    pub rms: f32,
 
-        let dev_name = default_device
-            .clone()
-            .name()
-            .expect("Failed to get device name");
+    /// How far the level is above the threshold, from 0.0 at the threshold to 1.0 at
+    /// `INTENSITY_RANGE_DB` above it, for mapping loudness to mouth openness or scale
+    pub intensity: f32,
 
-        input_device = Some(alsa_util::get_alsa_hint_for(&dev_name).map_or_else(
-            || InputDevice::new(default_device.clone(), dev_name.clone()),
-            |_| InputDevice::new(default_device.clone(), dev_name.clone()),
-        ));
+    /// Whether the input clipped after applying gain since the last event
+    pub clipping: bool,
+}
+
+/// Config values used on the audio thread
+///
+/// Copied out of the config so the audio callback neither allocates nor holds the config
+/// lock while processing
+#[derive(Clone, Copy)]
+struct DetectionSettings {
+    detection_mode: DetectionMode,
+    threshold_db: f32,
+    threshold_off_db: f32,
+    attack_ms: u32,
+    release_ms: u32,
+    hold_time_ms: u32,
+    min_open_ms: u32,
+    smoothing_window: usize,
+    input_gain: f32,
+    channel_mode: ChannelMode,
+    voice_filter: bool,
+    #[cfg_attr(not(feature = "rnnoise"), allow(dead_code))]
+    noise_suppression: bool,
+    flicker_input: bool,
+    flicker_min_ms: u32,
+    flicker_max_ms: u32,
+}
+
+impl From<&ChibiConfig> for DetectionSettings {
+    fn from(config: &ChibiConfig) -> Self {
+        Self {
+            detection_mode: config.detection_mode,
+            threshold_db: config.microphone_threshold_db,
+            threshold_off_db: config.threshold_off_db(),
+            attack_ms: config.attack_ms,
+            release_ms: config.release_ms,
+            hold_time_ms: config.hold_time_ms,
+            min_open_ms: config.min_open_ms,
+            smoothing_window: config.smoothing_window,
+            input_gain: config.input_gain,
+            channel_mode: config.channel_mode,
+            voice_filter: config.voice_filter,
+            noise_suppression: config.noise_suppression,
+            flicker_input: config.flicker_input,
+            flicker_min_ms: config.flicker_min_ms,
+            flicker_max_ms: config.flicker_max_ms,
+        }
     }
+}
 
-    // On any other platform, just use the device name
-    #[cfg(not(target_os = "linux"))]
-    {
-        let dev_name = default_device.name().expect("Failed to get device name");
-        input_device = Some(InputDevice::new(default_device, dev_name));
+/// Names of the audio hosts (backends) available on this platform, e.g. "ALSA" or "JACK"
+pub fn get_host_names() -> Vec<String> {
+    cpal::available_hosts()
+        .into_iter()
+        .map(|host_id| host_id.name().to_string())
+        .collect()
+}
+
+/// The audio host with the given name, or the platform's default host if no name is given
+/// or it isn't available
+pub fn get_host(name: Option<&str>) -> cpal::Host {
+    let Some(name) = name else {
+        return cpal::default_host();
+    };
+
+    let host = cpal::available_hosts()
+        .into_iter()
+        .find(|host_id| host_id.name().eq_ignore_ascii_case(name))
+        .and_then(|host_id| cpal::host_from_id(host_id).ok());
+
+    host.unwrap_or_else(|| {
+        warn!("Audio host '{}' is not available, using the default", name);
+        cpal::default_host()
+    })
+}
+
+/// Wrapper over `cpal::default_input_device` for the given host
+pub fn get_default_device(host_name: Option<&str>) -> Option<InputDevice> {
+    find_default_device(&get_input_devices(host_name), host_name)
+}
+
+/// The host's default input device, taken from an already listed set of devices so they
+/// don't have to be listed again
+pub fn find_default_device(
+    devices: &[InputDevice],
+    host_name: Option<&str>,
+) -> Option<InputDevice> {
+    let host = get_host(host_name);
+    let default_device = host.default_input_device()?;
+
+    let default_device = InputDevice::new(
+        default_device.clone(),
+        default_device.name().unwrap_or_else(|_| "Unknown".into()),
+    );
+
+    // Use the matching entry from the device list, so the default device has the same
+    // friendly name as it does there
+    devices
+        .iter()
+        .find(|&device| *device == default_device)
+        .cloned()
+        .or(Some(default_device))
+}
+
+/// How often the capture thread checks for stream errors
+const STREAM_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Time between attempts to rebuild a failed stream
+const STREAM_RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Most audio held in the shared sample buffer, in seconds. The capture thread drains it
+/// every `STREAM_POLL_INTERVAL`, so this is only reached if draining stalls, e.g. on a
+/// slow disk, in which case the oldest samples are dropped
+const MAX_BUFFERED_SECS: usize = 10;
+
+/// Failed attempts on the selected device before falling back to the default device
+const MAX_STREAM_RETRIES: u32 = 5;
+
+/// Sample rates offered for each supported range, besides the ends of the range
+const COMMON_SAMPLE_RATES: [u32; 3] = [44_100, 48_000, 96_000];
+
+/// Formats the device can be opened with, sorted by sample rate and channel count
+pub fn get_stream_formats(device: &Device) -> Vec<StreamFormat> {
+    let Ok(configs) = device.supported_input_configs() else {
+        return vec![];
+    };
+
+    let mut formats = vec![];
+    for range in configs {
+        let (min, max) = (range.min_sample_rate().0, range.max_sample_rate().0);
+
+        for sample_rate in [min, max].into_iter().chain(COMMON_SAMPLE_RATES) {
+            if (min..=max).contains(&sample_rate) {
+                formats.push(StreamFormat {
+                    sample_rate,
+                    channels: range.channels(),
+                });
+            }
+        }
     }
 
-    input_device
+    formats.sort();
+    formats.dedup();
+    formats
+}
+
+/// Stream config for the given format, falling back to the device's default config if
+/// no format is given or the device doesn't support it
+pub fn get_stream_config(
+    device: &Device,
+    format: Option<StreamFormat>,
+) -> Result<SupportedStreamConfig, DefaultStreamConfigError> {
+    let Some(format) = format else {
+        return device.default_input_config();
+    };
+
+    // Samples are always captured as f32, so prefer ranges that provide them directly
+    let range = device.supported_input_configs().ok().and_then(|configs| {
+        configs
+            .filter(|range| {
+                range.channels() == format.channels
+                    && (range.min_sample_rate().0..=range.max_sample_rate().0)
+                        .contains(&format.sample_rate)
+            })
+            .max_by_key(|range| range.sample_format() == SampleFormat::F32)
+    });
+
+    match range {
+        Some(range) => Ok(range.with_sample_rate(SampleRate(format.sample_rate))),
+        None => {
+            warn!(
+                "Input format {} is not supported, using the default",
+                format
+            );
+            device.default_input_config()
+        }
+    }
 }
 
 /// Return a list of input devices tagged with their friendly names
 ///
 /// On Linux, this will use the `alsa` crate to query hints
 /// On any other platform, this will use the device name as returned by `cpal`
-pub fn get_input_devices() -> Vec<InputDevice> {
+pub fn get_input_devices(host_name: Option<&str>) -> Vec<InputDevice> {
+    alsa_util::with_alsa_errors_logged(|| list_input_devices(host_name))
+}
+
+/// Names of the output devices of the given host, which the input can be monitored on
+pub fn get_output_device_names(host_name: Option<&str>) -> Vec<String> {
+    alsa_util::with_alsa_errors_logged(|| {
+        let devices = match get_host(host_name).output_devices() {
+            Ok(devices) => devices.collect::<Vec<_>>(),
+            Err(err) => {
+                warn!("Failed to list output devices: {}", err);
+                vec![]
+            }
+        };
+
+        devices
+            .iter()
+            .filter_map(|device| device.name().ok())
+            .collect()
+    })
+}
+
+fn list_input_devices(host_name: Option<&str>) -> Vec<InputDevice> {
     let input_devices: Vec<InputDevice>;
 
-    let host = cpal::default_host();
-    let devices: Vec<cpal::Device> = host
-        .input_devices()
-        .expect("No input devices found")
-        .collect();
+    let host = get_host(host_name);
+    let devices: Vec<cpal::Device> = match host.input_devices() {
+        Ok(devices) => devices.collect(),
+        Err(err) => {
+            warn!("Failed to list input devices: {}", err);
+            vec![]
+        }
+    };
 
     // On Linux query ALSA hints for the device description and use that
-    // This spams some ALSA warnings but it's (probably) fine:
-    // ALSA lib pcm_dsnoop.c:567:(snd_pcm_dsnoop_open) unable to open slave
-    // ALSA lib pcm_dmix.c:1000:(snd_pcm_dmix_open) unable to open slave
     #[cfg(target_os = "linux")]
     {
-        use crate::capture::alsa_util;
-
         let hints = alsa_util::get_alsa_hints();
         input_devices = devices
             .into_iter()
             .map(|dev| {
                 let dev_name = dev.name().unwrap_or_else(|_| "Unknown".into());
 
-                InputDevice::new(
-                    dev,
-                    match dev_name.to_lowercase() {
-                        s if s.contains("pipewire") => "Pipewire Media Server".to_string(),
-                        s if s.contains("pulse") => "PulseAudio".to_string(),
-                        _ => hints.get(&dev_name).cloned().unwrap_or(dev_name),
-                    },
-                )
+                let friendly_name = alsa_util::friendly_name(&dev_name, &hints);
+                InputDevice::new(dev, friendly_name)
             })
             .collect();
     }
@@ -127,106 +339,493 @@ pub fn get_input_devices() -> Vec<InputDevice> {
         input_devices = devices
             .iter()
             .map(|dev| {
-                let dev_name = dev.name().expect("Failed to get device name");
+                let dev_name = dev.name().unwrap_or_else(|_| "Unknown".into());
                 InputDevice::new(dev.clone(), dev_name)
             })
             .collect();
     }
 
+    for device in &input_devices {
+        debug!("Found input device {}", device);
+    }
+
     input_devices
 }
 
+/// Build an input stream that hands each block of samples to `process`
 fn capture_input(
+    input_device: &Device,
+    stream_config: SupportedStreamConfig,
+    mut process: impl FnMut(&[f32]) + Send + 'static,
+    errors: mpsc::Sender<cpal::StreamError>,
+) -> Result<cpal::Stream, cpal::BuildStreamError> {
+    // Errors are handled on the capture thread, which rebuilds the stream
+    let err_fn = move |err| {
+        errors.send(err).ok();
+    };
+
+    input_device.build_input_stream(
+        &stream_config.into(),
+        move |data: &[f32], _| process(data),
+        err_fn,
+        None,
+    )
+}
+
+/// Detection on blocks of interleaved samples, from a device or the test signal
+fn audio_processor(
     config: Arc<Mutex<ChibiConfig>>,
-    input_device: Arc<Mutex<Device>>,
-    input_config: Arc<Mutex<SupportedStreamConfig>>,
+    channels: usize,
+    input_sample_rate: u32,
     buffer: Arc<Mutex<Vec<i16>>>,
-
-    sender: Sender<bool>,
-) -> Result<cpal::Stream, cpal::BuildStreamError> {
+    levels_wanted: Arc<AtomicBool>,
+    mut monitor: Option<MonitorSink>,
+    sender: Sender<MicEvent>,
+) -> impl FnMut(&[f32]) + Send + 'static {
     // Future additions:
-    // TODO: Amplify the signal when we receive it, before calculating RMS
     // TODO: DSP processing so the signal is as clean as possible
+    let mut gate = NoiseGate::default();
+    let mut smoothing = MovingAverage::default();
+    let mut flicker = Flicker::default();
+
+    #[cfg(feature = "rnnoise")]
+    let mut denoiser = denoise::Denoiser::new();
+
+    let channels = channels.max(1);
+
+    // Everything after resampling runs at the internal rate, whatever the device uses
+    let mut resampler = Resampler::new(input_sample_rate, INTERNAL_SAMPLE_RATE);
+    let sample_rate = INTERNAL_SAMPLE_RATE as f32;
+    let mut voice_filter = VoiceFilter::new(INTERNAL_SAMPLE_RATE);
+
+    let mut settings = DetectionSettings::from(&*lock_and_unlock!(config));
+
+    // Changing the OSC settings takes effect the next time capture is started
+    let osc = {
+        let config = lock_and_unlock!(config);
+
+        if config.osc_enabled {
+            OscSender::new(&config.osc_address)
+                .inspect_err(|err| {
+                    warn!("Failed to set up OSC for {}: {}", config.osc_address, err)
+                })
+                .ok()
+        } else {
+            None
+        }
+    };
+    let mut was_active = false;
+    let mut clipped = false;
+    let mut since_update_ms = LEVEL_UPDATE_INTERVAL_MS;
+
+    // Scratch buffers reused between callbacks, so the audio thread doesn't allocate once
+    // they have grown to the block size
+    let mut input = Vec::<f32>::new();
+    let mut selected = Vec::<f32>::new();
+    let mut processed = Vec::<f32>::new();
+    #[cfg(feature = "rnnoise")]
+    let mut denoised = Vec::<f32>::new();
+
+    move |data: &[f32]| {
+        // Some hosts deliver empty buffers, which carry no level to measure
+        if data.is_empty() {
+            return;
+        }
+
+        // Pick up config changes without waiting on the lock if the UI is holding it
+        if let Ok(config) = config.try_lock() {
+            settings = DetectionSettings::from(&*config);
+        }
+
+        // Amplify the signal, clamping so it doesn't overflow when converted to i16
+        input.clear();
+        input.extend(data.iter().map(|&sample| {
+            let amplified = sample * settings.input_gain;
+            clipped |= amplified.abs() >= 1.0;
+            amplified.clamp(-1.0, 1.0)
+        }));
+
+        let mut processed_channels = channels;
+
+        // Only detect on the selected channel, so a silent channel doesn't drag the level down
+        if let Some(channel) = settings.channel_mode.channel_index()
+            && channels > 1
+        {
+            selected.clear();
+            extract_channel(&input, channels, channel, &mut selected);
+            std::mem::swap(&mut input, &mut selected);
+            processed_channels = 1;
+        }
+
+        processed.clear();
+        resampler.process(&input, processed_channels, &mut processed);
+
+        if processed.is_empty() {
+            return;
+        }
+
+        if settings.voice_filter {
+            voice_filter.process(&mut processed, processed_channels);
+        }
+
+        // Denoising outputs mono audio, one frame at a time. Until a full frame is
+        // available there is nothing new to measure
+        #[cfg(feature = "rnnoise")]
+        if settings.noise_suppression {
+            denoised.clear();
+            denoiser.process(&processed, processed_channels, &mut denoised);
+
+            if denoised.is_empty() {
+                return;
+            }
 
-    let err_fn = |err| eprintln!("Error in audio stream: {}", err);
-    let mut mic_active = false;
+            std::mem::swap(&mut processed, &mut denoised);
+            processed_channels = 1;
+        }
+
+        // Play back exactly what detection is about to measure
+        if let Some(monitor) = &mut monitor {
+            monitor.push(&processed, processed_channels);
+        }
 
-    input_device.lock().unwrap().build_input_stream(
-        &input_config.lock().unwrap().clone().into(),
-        move |data: &[f32], _| {
-            let config = lock_and_unlock!(config);
+        // Compute the level, both before and after processing. The processed level is
+        // averaged over the last few blocks so transients don't toggle the gate
+        let raw_rms = rms_amplitude(data);
+        let level = match settings.detection_mode {
+            DetectionMode::Rms => rms_amplitude(&processed),
+            DetectionMode::Peak => peak_amplitude(&processed),
+        };
+        let rms = smoothing.push(level, settings.smoothing_window);
+        let level_db = amplitude_to_db(rms);
+        let intensity = ((level_db - settings.threshold_db) / INTENSITY_RANGE_DB).clamp(0.0, 1.0);
+
+        let event = |active| MicEvent {
+            active,
+            raw_rms,
+            rms,
+            intensity,
+            clipping: clipped,
+        };
+
+        // Duration of this block of audio, used for the gate's attack and release
+        let elapsed_ms = (processed.len() / processed_channels) as f32 * 1000.0 / sample_rate;
+        let mic_active = gate.process(level_db, elapsed_ms, &settings);
+
+        let shown_active = if settings.flicker_input {
+            flicker.process(mic_active, elapsed_ms, &settings)
+        } else {
+            mic_active
+        };
+
+        // State changes are sent right away. Level updates are only sent a few times a
+        // second, and while idle only if the UI is showing the levels
+        since_update_ms += elapsed_ms;
+        let send_levels = (shown_active || levels_wanted.load(Ordering::Relaxed))
+            && since_update_ms >= LEVEL_UPDATE_INTERVAL_MS;
+
+        if shown_active != was_active || send_levels {
+            sender.try_send(event(shown_active)).ok();
+            clipped = false;
+            since_update_ms = 0.0;
+        }
 
-            let mut rng = rand::rng();
+        if shown_active != was_active {
+            if let Some(osc) = &osc {
+                osc.send_mic_active(shown_active);
+            }
 
-            // Compute RMS amplitude
-            let rms = rms_amplitude(data);
+            was_active = shown_active;
+        }
 
-            let rms_threshold_on = config.microphone_threshold;
-            let rms_threshold_off = rms_threshold_on * config.deadband_factor; // Hysteresis, aka "deadband"
+        // Only process audio if the microphone is active
+        if !mic_active {
+            return;
+        }
 
-            if mic_active {
-                if rms < rms_threshold_off {
-                    mic_active = false;
+        // Append samples to the shared buffer, mixed down to mono since the channel
+        // count can change with the settings
+        let mut buf = buffer.lock().unwrap();
+        buf.extend(processed.chunks_exact(processed_channels).map(|frame| {
+            let sample = frame.iter().sum::<f32>() / processed_channels as f32;
+            (sample * 32767.0) as i16
+        }));
+
+        let excess = buf
+            .len()
+            .saturating_sub(INTERNAL_SAMPLE_RATE as usize * MAX_BUFFERED_SECS);
+        if excess > 0 {
+            buf.drain(..excess);
+        }
+    }
+}
+
+/// A running capture stream and the thread that owns it
+///
+/// Dropping the session stops the stream and joins the thread, releasing the input device
+pub struct CaptureSession {
+    shutdown: Option<mpsc::Sender<()>>,
+    handle: Option<JoinHandle<()>>,
+    levels_wanted: Arc<AtomicBool>,
+}
+
+impl CaptureSession {
+    /// Whether level updates should be sent while the microphone is inactive, e.g. for
+    /// the meters in the settings. Otherwise only state changes are sent while idle
+    pub fn set_levels_wanted(&self, wanted: bool) {
+        self.levels_wanted.store(wanted, Ordering::Relaxed);
+    }
+}
+
+impl Drop for CaptureSession {
+    fn drop(&mut self) {
+        // Dropping the sender wakes up the capture thread, which then drops the stream
+        self.shutdown.take();
+
+        if let Some(handle) = self.handle.take() {
+            handle.join().ok();
+        }
+    }
+}
+
+pub fn spawn_capture_thread(
+    config: Arc<Mutex<ChibiConfig>>,
+    input_device: Arc<Mutex<Device>>,
+    input_config: Arc<Mutex<SupportedStreamConfig>>,
+    sender: Sender<MicEvent>,
+    notifier: Option<Sender<String>>,
+) -> CaptureSession {
+    let buffer = Arc::new(Mutex::new(Vec::<i16>::new()));
+    let levels_wanted = Arc::new(AtomicBool::new(false));
+    let (shutdown_sender, shutdown_receiver) = mpsc::channel::<()>();
+
+    // The stream is created and dropped on the capture thread, since it isn't `Send` on
+    // every platform
+    let stream_levels_wanted = levels_wanted.clone();
+    let handle = std::thread::spawn(move || {
+        let notify = |message: String| {
+            warn!("{}", message);
+
+            if let Some(notifier) = &notifier {
+                notifier.try_send(message).ok();
+            }
+        };
+
+        let mut failures = 0;
+        let mut recorder = start_recording(&config, &notify);
+
+        'capture: loop {
+            // Monitoring is optional, so capture carries on without it if the output
+            // device can't be opened
+            let (monitor_enabled, host_name, monitor_device) = {
+                let config = lock_and_unlock!(config);
+                (
+                    config.monitor_enabled,
+                    config.audio_host.clone(),
+                    config.monitor_device.clone(),
+                )
+            };
+
+            let (monitor, monitor_sink) = if monitor_enabled {
+                match Monitor::open(&get_host(host_name.as_deref()), monitor_device.as_deref()) {
+                    Ok((monitor, sink)) => (Some(monitor), Some(sink)),
+                    Err(err) => {
+                        if failures == 0 {
+                            notify(format!("Failed to start monitoring the input: {}", err));
+                        }
+                        (None, None)
+                    }
                 }
             } else {
-                if rms >= rms_threshold_on {
-                    mic_active = true;
+                (None, None)
+            };
+
+            let (error_sender, error_receiver) = mpsc::channel();
+            let stream_config = input_config.lock().unwrap().clone();
+            let process = audio_processor(
+                config.clone(),
+                stream_config.channels() as usize,
+                stream_config.sample_rate().0,
+                buffer.clone(),
+                stream_levels_wanted.clone(),
+                monitor_sink,
+                sender.clone(),
+            );
+
+            let stream = capture_input(
+                &input_device.lock().unwrap(),
+                stream_config,
+                process,
+                error_sender,
+            )
+            .map_err(|err| err.to_string())
+            .and_then(|stream| stream.play().map(|_| stream).map_err(|err| err.to_string()));
+
+            let error = match stream {
+                Ok(stream) => {
+                    if failures > 0 {
+                        notify("Reconnected to the input device".to_string());
+                        failures = 0;
+                    }
+
+                    // Run until the session is dropped or the stream fails, e.g. because
+                    // the device was unplugged
+                    let error = loop {
+                        let shutdown = wait_for_shutdown(&shutdown_receiver, STREAM_POLL_INTERVAL);
+                        drain_buffer(&buffer, &mut recorder, &notify);
+
+                        if shutdown {
+                            break 'capture;
+                        }
+
+                        if let Ok(err) = error_receiver.try_recv() {
+                            break err.to_string();
+                        }
+                    };
+
+                    drop(stream);
+                    drop(monitor);
+                    error
                 }
+                Err(err) => err,
+            };
+
+            failures += 1;
+            if failures == 1 {
+                notify(format!(
+                    "The input device stopped working ({}), reconnecting...",
+                    error
+                ));
             }
 
-            if mic_active {
-                if config.flicker_input {
-                    // Pick a random duration for the flicker to make it look more natural
-                    let random_duration = Duration::from_millis(rng.random_range(30..=100));
+            // The device may be gone for good, so try the default device instead
+            if failures == MAX_STREAM_RETRIES {
+                let host = get_host(lock_and_unlock!(config).audio_host.as_deref());
 
-                    sender.try_send(true).ok();
-                    std::thread::sleep(random_duration);
-                    sender.try_send(false).ok();
-                } else {
-                    sender.try_send(true).ok();
+                if let Some(device) = host.default_input_device()
+                    && let Ok(default_config) = device.default_input_config()
+                {
+                    *input_device.lock().unwrap() = device;
+                    *input_config.lock().unwrap() = default_config;
+                    notify("Switching to the default input device".to_string());
                 }
-            } else {
-                sender.try_send(false).ok();
             }
 
-            // Only process audio if the microphone is active
-            if !mic_active {
-                return;
+            if wait_for_shutdown(&shutdown_receiver, STREAM_RETRY_DELAY) {
+                break;
             }
+        }
 
-            let samples: Vec<i16> = data
-                .iter()
-                .map(|&sample| {
-                    let clamped = sample.max(-1.0).min(1.0);
-                    (clamped * 32767.0) as i16
-                })
-                .collect();
+        finish_recording(recorder);
+    });
 
-            // Append samples to the shared buffer
-            let mut buf = buffer.lock().unwrap();
-            buf.extend_from_slice(&samples);
-        },
-        err_fn,
-        None,
-    )
+    CaptureSession {
+        shutdown: Some(shutdown_sender),
+        handle: Some(handle),
+        levels_wanted,
+    }
 }
 
-pub fn spawn_capture_thread(
+/// Run detection on a generated test signal instead of an input device
+pub fn spawn_test_signal_thread(
     config: Arc<Mutex<ChibiConfig>>,
-    input_device: Arc<Mutex<Device>>,
-    input_config: Arc<Mutex<SupportedStreamConfig>>,
-    sender: Sender<bool>,
-) {
+    sender: Sender<MicEvent>,
+) -> CaptureSession {
     let buffer = Arc::new(Mutex::new(Vec::<i16>::new()));
+    let levels_wanted = Arc::new(AtomicBool::new(false));
+    let (shutdown_sender, shutdown_receiver) = mpsc::channel::<()>();
+
+    let mut process = audio_processor(
+        config.clone(),
+        1,
+        TestSignal::SAMPLE_RATE,
+        buffer.clone(),
+        levels_wanted.clone(),
+        None,
+        sender,
+    );
 
-    std::thread::spawn(move || {
-        let stream = capture_input(config, input_device, input_config, buffer.clone(), sender)
-            .expect("Failed to capture input stream");
+    let handle = std::thread::spawn(move || {
+        let mut signal = TestSignal::default();
+        let mut block = vec![0.0; (TestSignal::SAMPLE_RATE / 100) as usize];
 
-        stream.play().expect("Failed to play stream");
+        let notify = |message: String| warn!("{}", message);
+        let mut recorder = start_recording(&config, &notify);
 
+        // Blocks of 10 ms, paced roughly like a real device
         loop {
-            std::thread::sleep(std::time::Duration::from_millis(100));
+            signal.fill(&mut block);
+            process(&block);
+            drain_buffer(&buffer, &mut recorder, &notify);
+
+            if wait_for_shutdown(&shutdown_receiver, Duration::from_millis(10)) {
+                break;
+            }
         }
+
+        finish_recording(recorder);
     });
+
+    CaptureSession {
+        shutdown: Some(shutdown_sender),
+        handle: Some(handle),
+        levels_wanted,
+    }
+}
+
+/// Start recording to a new file if recording is enabled
+fn start_recording(config: &Mutex<ChibiConfig>, notify: &impl Fn(String)) -> Option<Recorder> {
+    let recordings_dir = {
+        let config = lock_and_unlock!(config);
+        config.record_to_wav.then(|| config.recordings_dir())
+    }?;
+
+    match Recorder::create(&recordings_dir) {
+        Ok(recorder) => {
+            notify(format!("Recording to {}", recorder.path().display()));
+            Some(recorder)
+        }
+        Err(err) => {
+            notify(format!(
+                "Failed to start recording in {}: {}",
+                recordings_dir.display(),
+                err
+            ));
+            None
+        }
+    }
+}
+
+/// Move the collected samples into the recording, or throw them away if not recording
+fn drain_buffer(
+    buffer: &Mutex<Vec<i16>>,
+    recorder: &mut Option<Recorder>,
+    notify: &impl Fn(String),
+) {
+    let Some(active_recorder) = recorder else {
+        buffer.lock().unwrap().clear();
+        return;
+    };
+
+    if let Err(err) = active_recorder.drain(buffer) {
+        notify(format!("Recording stopped: {}", err));
+
+        finish_recording(recorder.take());
+        buffer.lock().unwrap().clear();
+    }
+}
+
+fn finish_recording(recorder: Option<Recorder>) {
+    match recorder.map(Recorder::finish) {
+        Some(Ok(path)) => info!("Saved recording to {}", path.display()),
+        Some(Err(err)) => warn!("Failed to finish recording: {}", err),
+        None => {}
+    }
+}
+
+/// Wait up to `timeout`, returning whether the session was dropped in the meantime
+fn wait_for_shutdown(shutdown: &mpsc::Receiver<()>, timeout: Duration) -> bool {
+    !matches!(
+        shutdown.recv_timeout(timeout),
+        Err(mpsc::RecvTimeoutError::Timeout)
+    )
 }
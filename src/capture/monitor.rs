@@ -0,0 +1,106 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use crate::capture::resample::{INTERNAL_SAMPLE_RATE, Resampler};
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use log::warn;
+
+// Most audio kept queued for the output device. Anything older is dropped, so a slow
+// output device doesn't make the monitor fall further and further behind
+const MAX_LATENCY_MS: usize = 200;
+
+/// Playback of the processed input on an output device, for hearing what detection hears
+///
+/// The stream isn't `Send` on every platform, so this has to live on the capture thread.
+/// Playback stops when it is dropped
+pub struct Monitor {
+    _stream: cpal::Stream,
+}
+
+/// Feeds audio from the audio callback to a `Monitor`
+pub struct MonitorSink {
+    queue: Arc<Mutex<VecDeque<f32>>>,
+    max_len: usize,
+    resampler: Resampler,
+
+    // Scratch buffers reused between callbacks
+    mono: Vec<f32>,
+    resampled: Vec<f32>,
+}
+
+impl Monitor {
+    /// Start playing on the output device with the given name, or the host's default
+    /// output device if no name is given
+    pub fn open(
+        host: &cpal::Host,
+        device_name: Option<&str>,
+    ) -> Result<(Self, MonitorSink), String> {
+        let device = match device_name {
+            Some(name) => host
+                .output_devices()
+                .map_err(|err| err.to_string())?
+                .find(|device| device.name().is_ok_and(|dev_name| dev_name == name)),
+            None => host.default_output_device(),
+        }
+        .ok_or_else(|| "no output device found".to_string())?;
+
+        let output_config = device
+            .default_output_config()
+            .map_err(|err| err.to_string())?;
+        let channels = output_config.channels().max(1) as usize;
+        let sample_rate = output_config.sample_rate().0;
+
+        let queue = Arc::new(Mutex::new(VecDeque::<f32>::new()));
+        let output_queue = queue.clone();
+
+        let stream = device
+            .build_output_stream(
+                &output_config.into(),
+                move |data: &mut [f32], _| {
+                    let mut queue = output_queue.lock().unwrap();
+
+                    // The monitor is mono, so play the same sample on every channel. Play
+                    // silence when the input falls behind
+                    for frame in data.chunks_mut(channels) {
+                        frame.fill(queue.pop_front().unwrap_or(0.0));
+                    }
+                },
+                |err| warn!("Monitor output failed: {}", err),
+                None,
+            )
+            .map_err(|err| err.to_string())?;
+        stream.play().map_err(|err| err.to_string())?;
+
+        let sink = MonitorSink {
+            queue,
+            max_len: sample_rate as usize * MAX_LATENCY_MS / 1000,
+            resampler: Resampler::new(INTERNAL_SAMPLE_RATE, sample_rate),
+            mono: vec![],
+            resampled: vec![],
+        };
+
+        Ok((Self { _stream: stream }, sink))
+    }
+}
+
+impl MonitorSink {
+    /// Queue interleaved samples at the internal sample rate for playback
+    pub fn push(&mut self, data: &[f32], channels: usize) {
+        let channels = channels.max(1);
+
+        self.mono.clear();
+        self.mono.extend(
+            data.chunks_exact(channels)
+                .map(|frame| frame.iter().sum::<f32>() / channels as f32),
+        );
+
+        self.resampled.clear();
+        self.resampler.process(&self.mono, 1, &mut self.resampled);
+
+        let mut queue = self.queue.lock().unwrap();
+        queue.extend(&self.resampled);
+
+        let excess = queue.len().saturating_sub(self.max_len);
+        queue.drain(..excess);
+    }
+}
@@ -0,0 +1,50 @@
+use std::io;
+use std::net::UdpSocket;
+
+// OSC address the microphone state is sent to
+const MIC_ACTIVE_ADDRESS: &str = "/chibi/mic_active";
+
+/// Sends the microphone state over OSC, for driving other VTuber tools
+pub struct OscSender {
+    socket: UdpSocket,
+}
+
+impl OscSender {
+    /// Create a sender for `address` (e.g. "127.0.0.1:9000")
+    pub fn new(address: &str) -> io::Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(address)?;
+
+        // Never block the audio thread on the network
+        socket.set_nonblocking(true)?;
+
+        Ok(Self { socket })
+    }
+
+    /// Send whether the microphone is active, as 1.0 or 0.0
+    pub fn send_mic_active(&self, active: bool) {
+        let message = encode_float_message(MIC_ACTIVE_ADDRESS, if active { 1.0 } else { 0.0 });
+        self.socket.send(&message).ok();
+    }
+}
+
+/// Encode an OSC message with a single float argument
+fn encode_float_message(address: &str, value: f32) -> Vec<u8> {
+    let mut message = Vec::with_capacity(address.len() + 12);
+
+    push_osc_string(&mut message, address);
+    push_osc_string(&mut message, ",f");
+    message.extend_from_slice(&value.to_be_bytes());
+
+    message
+}
+
+/// Strings are null terminated and padded to a multiple of 4 bytes
+fn push_osc_string(message: &mut Vec<u8>, string: &str) {
+    message.extend_from_slice(string.as_bytes());
+    message.push(0);
+
+    while !message.len().is_multiple_of(4) {
+        message.push(0);
+    }
+}
@@ -0,0 +1,72 @@
+use std::fs::{self, File};
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hound::{SampleFormat, WavSpec, WavWriter};
+
+use crate::capture::resample::INTERNAL_SAMPLE_RATE;
+
+/// Records what the microphone picked up while it was active to a WAV file
+///
+/// The audio callback only appends to the shared buffer, the capture thread moves the
+/// samples from there into the file so the audio thread never waits on the disk
+pub struct Recorder {
+    writer: WavWriter<BufWriter<File>>,
+    path: PathBuf,
+
+    // Samples taken out of the shared buffer, reused between drains
+    scratch: Vec<i16>,
+}
+
+impl Recorder {
+    /// Start a new recording in `dir`, named after the current time
+    pub fn create(dir: &Path) -> hound::Result<Self> {
+        fs::create_dir_all(dir)?;
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        let path = dir.join(format!("chibi-{}.wav", timestamp));
+
+        // The buffer holds mono samples at the internal rate, whatever the device uses
+        let spec = WavSpec {
+            channels: 1,
+            sample_rate: INTERNAL_SAMPLE_RATE,
+            bits_per_sample: 16,
+            sample_format: SampleFormat::Int,
+        };
+
+        Ok(Self {
+            writer: WavWriter::create(&path, spec)?,
+            path,
+            scratch: vec![],
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Move the samples collected so far from the shared buffer into the recording
+    pub fn drain(&mut self, buffer: &Mutex<Vec<i16>>) -> hound::Result<()> {
+        // Swap buffers rather than writing under the lock, so the audio callback is
+        // never held up by the disk
+        std::mem::swap(&mut *buffer.lock().unwrap(), &mut self.scratch);
+
+        for &sample in &self.scratch {
+            self.writer.write_sample(sample)?;
+        }
+        self.scratch.clear();
+
+        Ok(())
+    }
+
+    /// Write out the header, after which the file can be played
+    pub fn finish(self) -> hound::Result<PathBuf> {
+        self.writer.finalize()?;
+        Ok(self.path)
+    }
+}
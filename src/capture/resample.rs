@@ -0,0 +1,119 @@
+/// Sample rate all audio is converted to before processing, so that time based
+/// processing behaves the same on every device
+pub const INTERNAL_SAMPLE_RATE: u32 = 48_000;
+
+/// Linear interpolation resampler for interleaved audio
+///
+/// Keeps the last frame of the previous block, so consecutive blocks join up without
+/// clicks. Good enough for level detection, but not for listening to
+pub struct Resampler {
+    // Input frames advanced per output frame
+    step: f64,
+
+    // Position of the next output frame in the current block, where -1 is the last frame
+    // of the previous block
+    position: f64,
+    last_frame: Vec<f32>,
+}
+
+impl Resampler {
+    pub fn new(input_rate: u32, output_rate: u32) -> Self {
+        Self {
+            step: input_rate as f64 / output_rate.max(1) as f64,
+            position: 0.0,
+            last_frame: vec![],
+        }
+    }
+
+    /// Resample interleaved samples, appending the result to `output`
+    pub fn process(&mut self, data: &[f32], channels: usize, output: &mut Vec<f32>) {
+        let channels = channels.max(1);
+        let frames = data.len() / channels;
+
+        if self.step == 1.0 {
+            output.extend_from_slice(&data[..frames * channels]);
+            return;
+        }
+
+        if frames == 0 {
+            return;
+        }
+
+        if self.last_frame.len() != channels {
+            self.last_frame = data[..channels].to_vec();
+        }
+
+        let sample = |frame: isize, channel: usize| {
+            if frame < 0 {
+                self.last_frame[channel]
+            } else {
+                data[frame as usize * channels + channel]
+            }
+        };
+
+        while self.position < (frames - 1) as f64 {
+            let index = self.position.floor();
+            let fraction = (self.position - index) as f32;
+            let index = index as isize;
+
+            for channel in 0..channels {
+                let current = sample(index, channel);
+                let next = sample(index + 1, channel);
+                output.push(current + (next - current) * fraction);
+            }
+
+            self.position += self.step;
+        }
+
+        self.position -= frames as f64;
+        self.last_frame
+            .copy_from_slice(&data[(frames - 1) * channels..frames * channels]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_rate_passes_whole_frames_through() {
+        let mut resampler = Resampler::new(48_000, 48_000);
+        let mut output = vec![];
+
+        resampler.process(&[0.1, 0.2, 0.3, 0.4, 0.5], 2, &mut output);
+        assert_eq!(output, [0.1, 0.2, 0.3, 0.4]);
+    }
+
+    #[test]
+    fn upsampling_interpolates_across_blocks() {
+        let mut resampler = Resampler::new(24_000, 48_000);
+        let mut output = vec![];
+
+        resampler.process(&[0.0, 1.0, 2.0, 3.0], 1, &mut output);
+        resampler.process(&[4.0, 5.0], 1, &mut output);
+
+        // The second block picks up from the last frame of the first
+        assert_eq!(output, [0.0, 0.5, 1.0, 1.5, 2.0, 2.5, 3.0, 3.5, 4.0, 4.5]);
+    }
+
+    #[test]
+    fn downsampling_skips_frames() {
+        let mut resampler = Resampler::new(96_000, 48_000);
+        let mut output = vec![];
+
+        let ramp: Vec<f32> = (0..16).map(|i| i as f32).collect();
+        resampler.process(&ramp[..8], 1, &mut output);
+        resampler.process(&ramp[8..], 1, &mut output);
+
+        assert_eq!(output, [0.0, 2.0, 4.0, 6.0, 8.0, 10.0, 12.0, 14.0]);
+    }
+
+    #[test]
+    fn channels_are_interpolated_separately() {
+        let mut resampler = Resampler::new(24_000, 48_000);
+        let mut output = vec![];
+
+        resampler.process(&[0.0, 10.0, 1.0, 11.0], 2, &mut output);
+        assert_eq!(output, [0.0, 10.0, 0.5, 10.5]);
+    }
+}
@@ -0,0 +1,51 @@
+use std::f32::consts::TAU;
+
+use rand::Rng;
+
+use crate::capture::resample::INTERNAL_SAMPLE_RATE;
+
+// Length of a burst of "speech", and of the silence after it
+const BURST_MS: u32 = 1500;
+const PAUSE_MS: u32 = 1500;
+
+// Pitch of the tone in a burst, and how often its volume wobbles like syllables
+const TONE_HZ: f32 = 220.0;
+const SYLLABLE_HZ: f32 = 4.0;
+
+// Background noise between bursts, well below the default threshold
+const NOISE_AMPLITUDE: f32 = 0.005;
+
+/// Synthetic microphone signal, alternating bursts of tone with quiet noise
+///
+/// Drives the whole detection path without an input device, for demos and testing
+#[derive(Default)]
+pub struct TestSignal {
+    // Position in samples, at the internal sample rate
+    position: u64,
+}
+
+impl TestSignal {
+    pub const SAMPLE_RATE: u32 = INTERNAL_SAMPLE_RATE;
+
+    /// Fill `output` with the next mono samples of the signal
+    pub fn fill(&mut self, output: &mut [f32]) {
+        let sample_rate = Self::SAMPLE_RATE as f32;
+        let period = (BURST_MS + PAUSE_MS) as u64 * Self::SAMPLE_RATE as u64 / 1000;
+        let burst = BURST_MS as u64 * Self::SAMPLE_RATE as u64 / 1000;
+        let mut rng = rand::rng();
+
+        for sample in output {
+            let noise = rng.random_range(-NOISE_AMPLITUDE..=NOISE_AMPLITUDE);
+            let time = self.position as f32 / sample_rate;
+
+            *sample = if self.position % period < burst {
+                let volume = 0.3 + 0.2 * (TAU * SYLLABLE_HZ * time).sin();
+                volume * (TAU * TONE_HZ * time).sin() + noise
+            } else {
+                noise
+            };
+
+            self.position += 1;
+        }
+    }
+}
@@ -1,70 +1,897 @@
-use core::fmt;
-use std::fs;
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, Ipv4Addr};
+use std::ops::{Bound, RangeInclusive};
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+use std::{fmt, fs, io};
 
+use directories::ProjectDirs;
+use log::{debug, info, warn};
+
+use crate::capture::dsp::amplitude_to_db;
+use crate::keybinds::Keybinds;
 use serde::ser::Error as SerdeError;
 use serde::{Deserialize, Serialize};
 
+const CONFIG_FILE: &str = "config.toml";
+
+// Config file given on the command line, used instead of the default location
+static CONFIG_PATH_OVERRIDE: OnceLock<PathBuf> = OnceLock::new();
+
+/// Version of the config format, bumped whenever existing fields change meaning
+///
+/// 0. Unversioned, with a linear `microphone_threshold`
+/// 1. Threshold stored in dB as `microphone_threshold_db`
+/// 2. Lifetime speaking time stored in milliseconds as `total_speaking_ms`, instead of
+///    whole seconds as `total_speaking_secs`
+/// 3. Loudness thresholds stored in dB as `loudness_thresholds_db`, instead of linear
+///    `loudness_thresholds`
+const CONFIG_VERSION: u32 = 3;
+
+/// Error loading or saving the config file
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    Serialize(toml::ser::Error),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(err) => write!(f, "{}", err),
+            ConfigError::Parse(err) => write!(f, "invalid config: {}", err),
+            ConfigError::Serialize(err) => write!(f, "failed to serialize config: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<io::Error> for ConfigError {
+    fn from(err: io::Error) -> Self {
+        ConfigError::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(err: toml::de::Error) -> Self {
+        ConfigError::Parse(err)
+    }
+}
+
+impl From<toml::ser::Error> for ConfigError {
+    fn from(err: toml::ser::Error) -> Self {
+        ConfigError::Serialize(err)
+    }
+}
+
+/// Which channel of a multi-channel input device is used for detection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChannelMode {
+    Left,
+    Right,
+    #[default]
+    Mixed,
+}
+
+impl ChannelMode {
+    pub const ALL: [ChannelMode; 3] = [ChannelMode::Left, ChannelMode::Right, ChannelMode::Mixed];
+
+    /// Index of the channel to use, or `None` to use every channel
+    pub fn channel_index(self) -> Option<usize> {
+        match self {
+            ChannelMode::Left => Some(0),
+            ChannelMode::Right => Some(1),
+            ChannelMode::Mixed => None,
+        }
+    }
+}
+
+impl fmt::Display for ChannelMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChannelMode::Left => write!(f, "Left"),
+            ChannelMode::Right => write!(f, "Right"),
+            ChannelMode::Mixed => write!(f, "Mixed"),
+        }
+    }
+}
+
+/// Which level of the signal is compared against the threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DetectionMode {
+    /// RMS amplitude, steady but slow to react to sharp consonants
+    #[default]
+    Rms,
+    /// Peak amplitude, reacts to transients straight away
+    Peak,
+}
+
+impl DetectionMode {
+    pub const ALL: [DetectionMode; 2] = [DetectionMode::Rms, DetectionMode::Peak];
+}
+
+impl fmt::Display for DetectionMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DetectionMode::Rms => write!(f, "RMS (steady)"),
+            DetectionMode::Peak => write!(f, "Peak (fast)"),
+        }
+    }
+}
+
+/// How the level the microphone deactivates at is set
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeadbandMode {
+    /// A fraction of the threshold, so it follows the threshold when that changes
+    #[default]
+    Factor,
+    /// A level in dB of its own
+    Absolute,
+}
+
+impl DeadbandMode {
+    pub const ALL: [DeadbandMode; 2] = [DeadbandMode::Factor, DeadbandMode::Absolute];
+}
+
+impl fmt::Display for DeadbandMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeadbandMode::Factor => write!(f, "Relative to threshold"),
+            DeadbandMode::Absolute => write!(f, "Absolute level"),
+        }
+    }
+}
+
+/// Rendering backend used for the window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RendererBackend {
+    /// Use the GPU if available, falling back to software rendering
+    #[default]
+    Auto,
+    Wgpu,
+    /// Software rendering with tiny-skia, for old GPUs and virtual machines
+    Software,
+}
+
+impl RendererBackend {
+    pub const ALL: [RendererBackend; 3] = [
+        RendererBackend::Auto,
+        RendererBackend::Wgpu,
+        RendererBackend::Software,
+    ];
+
+    /// Backend name understood by iced's `ICED_BACKEND` variable, or `None` to let iced
+    /// pick
+    pub fn iced_backend(self) -> Option<&'static str> {
+        match self {
+            RendererBackend::Auto => None,
+            RendererBackend::Wgpu => Some("wgpu"),
+            RendererBackend::Software => Some("tiny-skia"),
+        }
+    }
+}
+
+impl fmt::Display for RendererBackend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RendererBackend::Auto => write!(f, "Automatic"),
+            RendererBackend::Wgpu => write!(f, "GPU (wgpu)"),
+            RendererBackend::Software => write!(f, "Software (tiny-skia)"),
+        }
+    }
+}
+
+/// Sample rate and channel count to open an input device with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct StreamFormat {
+    pub sample_rate: u32,
+    pub channels: u16,
+}
+
+impl fmt::Display for StreamFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} Hz, {} channel(s)", self.sample_rate, self.channels)
+    }
+}
+
+/// Avatar and detection settings that can be switched between at runtime, e.g. to use a
+/// different avatar for different streams
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Profile {
+    pub assets_path: PathBuf,
+
+    pub detection_mode: DetectionMode,
+
+    #[serde(serialize_with = "round_to_hundredths")]
+    pub microphone_threshold_db: f32,
+
+    #[serde(serialize_with = "round_to_hundredths")]
+    pub deadband_factor: f32,
+
+    pub deadband_mode: DeadbandMode,
+
+    #[serde(serialize_with = "round_to_hundredths")]
+    pub off_threshold_db: f32,
+
+    pub flicker_input: bool,
+}
+
+impl From<&ChibiConfig> for Profile {
+    fn from(config: &ChibiConfig) -> Self {
+        Self {
+            assets_path: config.assets_path.clone(),
+            detection_mode: config.detection_mode,
+            microphone_threshold_db: config.microphone_threshold_db,
+            deadband_factor: config.deadband_factor,
+            deadband_mode: config.deadband_mode,
+            off_threshold_db: config.off_threshold_db,
+            flicker_input: config.flicker_input,
+        }
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self::from(&ChibiConfig::default())
+    }
+}
+
 // Application configuration
 #[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct ChibiConfig {
-    // TODO: Implement rnnoise as an optional feature, although it will increase
-    // latency potentially
-    /// Microphone detection threshold (RMS amplitude)
+    /// Version of the config format, configs without one are treated as version 0
+    #[serde(default)]
+    pub version: u32,
+
+    /// Whether the RMS or the peak amplitude of the signal is compared against the
+    /// threshold. Peak levels run higher, so the threshold usually needs raising with it
+    pub detection_mode: DetectionMode,
+
+    /// Microphone detection threshold in dB relative to full scale
     #[serde(serialize_with = "round_to_hundredths")]
-    pub microphone_threshold: f32,
+    pub microphone_threshold_db: f32,
+
+    /// Threshold as an RMS amplitude, used by version 0. Converted to
+    /// `microphone_threshold_db` when migrating
+    #[serde(skip_serializing)]
+    microphone_threshold: Option<f32>,
 
     /// Deadband that determines when the microphone stays active prior to a signal drop off
-    #[serde(serialize_with = "round_to_hundredths")]
+    #[serde(
+        alias = "hysteresis_factor",
+        alias = "hysteris_factor",
+        serialize_with = "round_to_hundredths"
+    )]
     pub deadband_factor: f32,
 
+    /// Whether the microphone deactivates below `deadband_factor` times the threshold, or
+    /// below `off_threshold_db`
+    pub deadband_mode: DeadbandMode,
+
+    /// Level in dB the microphone deactivates below, in the absolute deadband mode. Never
+    /// above the threshold
+    #[serde(serialize_with = "round_to_hundredths")]
+    pub off_threshold_db: f32,
+
+    /// How long the signal must stay above the threshold before the microphone activates
+    pub attack_ms: u32,
+
+    /// How long the signal must stay below the deadband before the microphone deactivates
+    pub release_ms: u32,
+
+    /// Minimum time the microphone stays active after the signal was last above the
+    /// threshold, so short pauses between words don't close the mouth
+    pub hold_time_ms: u32,
+
+    /// Minimum time the signal must stay above the threshold before the microphone
+    /// activates, so a single loud transient like a door slam or keyboard clack doesn't
+    /// flash the mouth open. Meant to be set to the length of the noises to ignore, while
+    /// the attack time stays short
+    pub min_open_ms: u32,
+
+    /// Number of audio blocks the RMS is averaged over before being compared against
+    /// the threshold. Higher values reduce jitter, but react slower
+    pub smoothing_window: usize,
+
+    /// Band-pass filter the input to the frequencies of the human voice (roughly
+    /// 100Hz to 4kHz) before calculating RMS, so rumble and hiss are ignored
+    pub voice_filter: bool,
+
+    /// Run the input through RNNoise before calculating RMS. Only has an effect when
+    /// built with the `rnnoise` feature, and adds up to 10ms of latency
+    pub noise_suppression: bool,
+
+    /// Levels in dB relative to full scale above which the next avatar image is used while
+    /// talking (e.g. a threshold of -6.0 switches to the third image for loud speech)
+    pub loudness_thresholds_db: Vec<f32>,
+
+    /// Thresholds as RMS amplitudes, used up to version 2. Converted to
+    /// `loudness_thresholds_db` when migrating
+    #[serde(skip_serializing)]
+    loudness_thresholds: Option<Vec<f32>>,
+
+    /// Friendly name of the selected input device. The default device is used if unset
+    /// or if the device can't be found
+    pub input_device: Option<String>,
+
+    /// Name of the audio host (backend) to use, e.g. "ALSA", "JACK" or "WASAPI". The
+    /// platform's default host is used if unset
+    pub audio_host: Option<String>,
+
+    /// Record what the microphone picked up while it was active to a WAV file, one per
+    /// session
+    pub record_to_wav: bool,
+
+    /// Folder to save recordings to. A `recordings` folder in the platform's data
+    /// directory is used if unset
+    pub recordings_path: Option<PathBuf>,
+
+    /// Play the processed input back on `monitor_device`, to hear what detection hears.
+    /// Use headphones, on speakers this feeds back into the microphone
+    pub monitor_enabled: bool,
+
+    /// Name of the output device to monitor on. The default output device is used if
+    /// unset or if the device can't be found
+    pub monitor_device: Option<String>,
+
+    /// Format to open the input device with. The device's default format is used if unset
+    /// or if the device doesn't support it
+    pub input_format: Option<StreamFormat>,
+
+    /// Channel of a stereo input device to detect on, for interfaces where the
+    /// microphone is only on one side
+    pub channel_mode: ChannelMode,
+
     /// Can appear more visually appealing, but less accurate
     pub flicker_input: bool,
+
+    /// Range of the random time between flickers in milliseconds
+    pub flicker_min_ms: u32,
+    pub flicker_max_ms: u32,
+
+    /// Gain applied to the input signal before calculating RMS
+    #[serde(serialize_with = "round_to_hundredths")]
+    pub input_gain: f32,
+
+    /// Show the chroma key background instead of the theme's
+    pub chroma_key: bool,
+
+    /// Background color (RGB) shown when chroma key is toggled
+    pub chroma_key_color: [f32; 3],
+
+    /// Make the avatar's partly transparent edges fully opaque or fully transparent, so
+    /// they don't pick up the chroma key color and leave a fringe after keying
+    pub chroma_matte: bool,
+
+    /// Opacity (0 to 1) from which edge pixels are kept when `chroma_matte` is on. Lower
+    /// values keep more of the edges
+    pub chroma_matte_threshold: f32,
+
+    /// Show the status text and buttons below the avatar
+    pub show_buttons: bool,
+
+    /// Make the window background transparent while showing the avatar. Depends on the
+    /// compositor, and falls back to a solid background where unsupported
+    pub transparent_background: bool,
+
+    /// Size of the window, restored on startup
+    pub window_width: f32,
+    pub window_height: f32,
+
+    /// Position of the window, restored on startup if set
+    pub window_position: Option<[f32; 2]>,
+
+    /// Monitor to open the window on, by its number in the settings starting from 0. The
+    /// window is centered on it unless the saved position is already on it
+    pub window_monitor: Option<usize>,
+
+    /// Keep the window above other windows
+    pub always_on_top: bool,
+
+    /// Show only the avatar in a small window without decorations, e.g. for a corner of
+    /// the screen. The regular window size is kept for when mini mode is left
+    pub mini_mode: bool,
+
+    /// Width and height of the window in mini mode
+    pub mini_size: f32,
+
+    /// Keys bound to the UI toggles
+    pub keybinds: Keybinds,
+
+    /// Ask for confirmation before closing the window
+    pub confirm_quit: bool,
+
+    /// Rendering backend, in case the window stays blank with the default one. Takes
+    /// effect on the next launch, and is overridden by the `ICED_BACKEND` variable
+    pub renderer: RendererBackend,
+
+    /// Name of the built-in iced theme used for the interface, e.g. "Tokyo Night" or "Light"
+    pub theme: String,
+
+    /// Hide the window to the tray instead of quitting when it is closed. Only has an
+    /// effect when built with the `tray` feature
+    pub minimize_to_tray: bool,
+
+    /// Size of the avatar relative to the space available in the window
+    #[serde(serialize_with = "round_to_hundredths")]
+    pub avatar_scale: f32,
+
+    /// Flip the avatar images horizontally, for art facing the wrong way
+    pub mirror_horizontal: bool,
+
+    /// Offset of the avatar from the center of the window in pixels, set by dragging it
+    pub avatar_offset: [f32; 2],
+
+    /// Gently pulse the avatar while idle
+    pub breathing_enabled: bool,
+
+    /// Duration of a single breath in milliseconds
+    pub breathing_period_ms: u32,
+
+    /// How much the avatar shrinks at the bottom of a breath, as a fraction of its size
+    #[serde(serialize_with = "round_to_hundredths")]
+    pub breathing_amplitude: f32,
+
+    /// How much the avatar grows with the voice volume while talking, as a fraction of its
+    /// size. 0 disables the bounce
+    #[serde(serialize_with = "round_to_hundredths")]
+    pub bounce_intensity: f32,
+
+    /// Folder containing the avatar images
+    pub assets_path: PathBuf,
+
+    /// Blink at random intervals, using the "blink" and "talking_blink" state frames
+    pub blink_enabled: bool,
+
+    /// Range of the random interval between blinks in milliseconds
+    pub blink_interval_min_ms: u32,
+    pub blink_interval_max_ms: u32,
+
+    /// Optional image shown at startup until the microphone is first detected
+    pub splash_image: Option<PathBuf>,
+
+    /// Numbers (from 0) of the avatar images shown while idle and while talking, for
+    /// states without an image named after them. Louder talking uses the images after the
+    /// talking one
+    pub closed_image_index: usize,
+    pub open_image_index: usize,
+
+    /// Number (from 0) of the avatar image held while the avatar is frozen, e.g. a "BRB"
+    /// pose. The idle image is held if there is no such image
+    pub freeze_image: usize,
+
+    /// How long each frame of an animated state is shown in milliseconds
+    pub frame_duration_ms: u32,
+
+    /// Duration of the fade between images when the talking state changes, in
+    /// milliseconds. 0 swaps the images instantly
+    pub crossfade_ms: u32,
+
+    /// Optional folder of frames for each avatar state (e.g. "idle", "talking"),
+    /// used instead of the images in the assets folder. Folders with several images
+    /// (or animated GIFs) are played back as an animation
+    pub state_dirs: HashMap<String, PathBuf>,
+
+    /// Send the microphone state over OSC to `osc_address` (host:port) whenever it changes
+    pub osc_enabled: bool,
+    pub osc_address: String,
+
+    /// Broadcast the microphone state as JSON to WebSocket clients on `websocket_port`,
+    /// e.g. for browser source overlays
+    pub websocket_enabled: bool,
+    pub websocket_port: u16,
+
+    /// Address the WebSocket server listens on. Only this machine by default, which is
+    /// all a browser source needs
+    pub websocket_bind_address: IpAddr,
+
+    /// Serve the detection state in the Prometheus text format over HTTP on
+    /// `metrics_port`, for monitoring
+    pub metrics_enabled: bool,
+    pub metrics_port: u16,
+
+    /// Address the metrics server listens on. Only this machine by default, use
+    /// `0.0.0.0` to allow scrapes from the network
+    pub metrics_bind_address: IpAddr,
+
+    /// Publish the window as an NDI source named `ndi_name` at up to `ndi_fps` frames per
+    /// second. Only available when built with the `ndi` feature, and needs the NDI runtime
+    pub ndi_enabled: bool,
+    pub ndi_name: String,
+    pub ndi_fps: u32,
+
+    /// Total time the microphone has been active, over every session, in milliseconds
+    pub total_speaking_ms: u64,
+
+    /// Total time in whole seconds, used by version 1. Converted to `total_speaking_ms`
+    /// when migrating
+    #[serde(skip_serializing)]
+    total_speaking_secs: Option<u64>,
+
+    /// Named profiles, each with its own avatar and detection settings
+    pub profiles: BTreeMap<String, Profile>,
+
+    /// Profile whose settings are currently in use
+    pub active_profile: Option<String>,
 }
 
 impl ChibiConfig {
-    pub fn new(microphone_threshold: f32) -> Self {
+    pub fn new(microphone_threshold_db: f32) -> Self {
         Self {
-            microphone_threshold,
+            microphone_threshold_db,
             ..Default::default()
         }
     }
 
-    pub fn load(&mut self) {
+    /// Use `path` as the config file instead of the default location. Only the first
+    /// call has an effect
+    pub fn set_path(path: PathBuf) {
+        CONFIG_PATH_OVERRIDE.set(path).ok();
+    }
+
+    /// Path to `config.toml` in the platform's config directory, falling back to the
+    /// current directory if it can't be determined
+    pub fn path() -> PathBuf {
+        if let Some(path) = CONFIG_PATH_OVERRIDE.get() {
+            return path.clone();
+        }
+
+        ProjectDirs::from("", "", "chibi")
+            .map(|dirs| dirs.config_dir().join(CONFIG_FILE))
+            .unwrap_or_else(|| PathBuf::from(CONFIG_FILE))
+    }
+
+    /// Folder recordings are saved to, `recordings_path` if set or a folder in the
+    /// platform's data directory
+    pub fn recordings_dir(&self) -> PathBuf {
+        if let Some(path) = &self.recordings_path {
+            return path.clone();
+        }
+
+        ProjectDirs::from("", "", "chibi")
+            .map(|dirs| dirs.data_dir().join("recordings"))
+            .unwrap_or_else(|| PathBuf::from("recordings"))
+    }
+
+    /// Whether a config file has been written before, here or in the legacy location.
+    /// If not, this is the first time chibi is run
+    pub fn exists() -> bool {
+        Self::path().exists() || Path::new(CONFIG_FILE).is_file()
+    }
+
+    /// Load the config from `config.toml`, creating it if it doesn't exist
+    ///
+    /// Missing fields fall back to their defaults. On error, the current values are kept
+    pub fn load(&mut self) -> Result<(), ConfigError> {
+        let path = Self::path();
+
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        // A config given on the command line is used as is
+        if CONFIG_PATH_OVERRIDE.get().is_none() {
+            migrate_legacy_config(&path)?;
+        }
+
         // Create the config file if it doesn't exist
-        if !fs::metadata("config.toml").is_ok() {
-            println!("config.toml not found, creating a new one");
-            fs::write("config.toml", toml::to_string(self).unwrap()).unwrap();
+        if fs::metadata(&path).is_err() {
+            info!("{} not found, creating a new one", path.display());
+            fs::write(&path, toml::to_string(self)?)?;
         }
 
         // Load the config file
-        let config_file = fs::read_to_string("config.toml").ok();
-        if config_file.is_none() {
-            println!("Failed to read config.toml");
+        let config_file = fs::read_to_string(&path)?;
+        *self = toml::from_str(&config_file)?;
+
+        self.migrate();
+        self.validate();
+
+        debug!("Loaded {} successfully", path.display());
+        Ok(())
+    }
+
+    /// Add a stretch of talking to the lifetime total, keeping fractions of a second
+    pub fn add_speaking_time(&mut self, talked: Duration) {
+        self.total_speaking_ms = self
+            .total_speaking_ms
+            .saturating_add(talked.as_millis() as u64);
+    }
+
+    /// Level in dB the microphone has to drop below before it deactivates
+    pub fn threshold_off_db(&self) -> f32 {
+        match self.deadband_mode {
+            // The deadband factor scales the amplitude, which is an offset on the dB scale
+            DeadbandMode::Factor => {
+                self.microphone_threshold_db + amplitude_to_db(self.deadband_factor)
+            }
+            DeadbandMode::Absolute => self.off_threshold_db.min(self.microphone_threshold_db),
+        }
+    }
+
+    /// Name of the profile after the active one, wrapping around to the first. The first
+    /// profile if none is active, or `None` if there are no profiles
+    pub fn next_profile(&self) -> Option<String> {
+        let after_active = self.active_profile.as_ref().and_then(|active| {
+            self.profiles
+                .range::<String, _>((Bound::Excluded(active), Bound::Unbounded))
+                .next()
+        });
+
+        after_active
+            .or_else(|| self.profiles.iter().next())
+            .map(|(name, _)| name.clone())
+    }
+
+    /// Switch to the named profile, keeping the current settings in the profile being
+    /// switched away from. Returns false if there is no such profile
+    pub fn switch_profile(&mut self, name: &str) -> bool {
+        let Some(profile) = self.profiles.get(name).cloned() else {
+            return false;
+        };
+
+        let current = Profile::from(&*self);
+        if let Some(active) = &self.active_profile
+            && let Some(active_profile) = self.profiles.get_mut(active)
+        {
+            *active_profile = current;
+        }
+
+        self.assets_path = profile.assets_path;
+        self.detection_mode = profile.detection_mode;
+        self.microphone_threshold_db = profile.microphone_threshold_db;
+        self.deadband_factor = profile.deadband_factor;
+        self.deadband_mode = profile.deadband_mode;
+        self.off_threshold_db = profile.off_threshold_db;
+        self.flicker_input = profile.flicker_input;
+        self.active_profile = Some(name.to_string());
+
+        true
+    }
+
+    /// Upgrade a config written by an older version. Fields that didn't exist yet have
+    /// already been filled in with their defaults, and the new version is written on save
+    fn migrate(&mut self) {
+        if self.version > CONFIG_VERSION {
+            warn!(
+                "Config version {} is newer than this version of chibi ({}), some settings may be ignored",
+                self.version, CONFIG_VERSION
+            );
+            return;
+        }
+
+        if self.version == CONFIG_VERSION {
             return;
         }
 
-        println!("Loaded config.toml successfully");
-        let config: ChibiConfig = toml::from_str(config_file.as_deref().unwrap()).unwrap();
-        self.microphone_threshold = config.microphone_threshold;
-        self.deadband_factor = config.deadband_factor;
-        self.flicker_input = config.flicker_input;
+        if self.version < 1
+            && let Some(threshold) = self.microphone_threshold.take()
+        {
+            self.microphone_threshold_db = amplitude_to_db(threshold);
+        }
+
+        if self.version < 2
+            && let Some(secs) = self.total_speaking_secs.take()
+        {
+            self.total_speaking_ms = secs.saturating_mul(1000);
+        }
+
+        if self.version < 3
+            && let Some(thresholds) = self.loudness_thresholds.take()
+        {
+            self.loudness_thresholds_db = thresholds
+                .into_iter()
+                .map(|threshold| (amplitude_to_db(threshold) * 10.0).round() / 10.0)
+                .collect();
+        }
+
+        info!(
+            "Migrated config from version {} to {}",
+            self.version, CONFIG_VERSION
+        );
+        self.version = CONFIG_VERSION;
+    }
+
+    /// Clamp values that may have been edited by hand into their valid ranges
+    pub fn validate(&mut self) {
+        clamp_field(
+            "microphone_threshold_db",
+            &mut self.microphone_threshold_db,
+            -60.0..=0.0,
+        );
+        clamp_field("deadband_factor", &mut self.deadband_factor, 0.0..=1.0);
+        clamp_field("off_threshold_db", &mut self.off_threshold_db, -60.0..=0.0);
+        clamp_field("input_gain", &mut self.input_gain, 1.0..=10.0);
+        clamp_field("avatar_scale", &mut self.avatar_scale, 0.1..=1.0);
+        clamp_field("bounce_intensity", &mut self.bounce_intensity, 0.0..=0.5);
+        clamp_field("mini_size", &mut self.mini_size, 50.0..=1000.0);
+        clamp_field(
+            "chroma_matte_threshold",
+            &mut self.chroma_matte_threshold,
+            0.0..=1.0,
+        );
+        clamp_field(
+            "breathing_amplitude",
+            &mut self.breathing_amplitude,
+            0.0..=0.2,
+        );
+        clamp_int_field("crossfade_ms", &mut self.crossfade_ms, 0..=2000);
+        clamp_int_field("smoothing_window", &mut self.smoothing_window, 1..=50);
+
+        // Tiers are counted by how many thresholds the level reaches, so they have to be
+        // in the level's range and in increasing order
+        for threshold_db in &mut self.loudness_thresholds_db {
+            clamp_field("loudness_thresholds_db", threshold_db, -60.0..=0.0);
+        }
+        if !self.loudness_thresholds_db.is_sorted() {
+            warn!("loudness_thresholds_db isn't in increasing order, sorting it");
+            self.loudness_thresholds_db.sort_by(f32::total_cmp);
+        }
+
+        if self.flicker_min_ms > self.flicker_max_ms {
+            warn!(
+                "flicker_min_ms = {} is above flicker_max_ms = {}, using {} for both",
+                self.flicker_min_ms, self.flicker_max_ms, self.flicker_min_ms
+            );
+            self.flicker_max_ms = self.flicker_min_ms;
+        }
     }
 
     pub fn save(&self) {
-        fs::write("config.toml", toml::to_string(self).unwrap()).expect("Failed to save config");
+        fs::write(Self::path(), toml::to_string(self).unwrap()).expect("Failed to save config");
     }
 }
 
 impl Default for ChibiConfig {
     fn default() -> Self {
         Self {
-            microphone_threshold: 0.12,
+            version: CONFIG_VERSION,
+            detection_mode: DetectionMode::Rms,
+            microphone_threshold_db: -18.0,
+            microphone_threshold: None,
             deadband_factor: 0.30,
+            deadband_mode: DeadbandMode::Factor,
+            off_threshold_db: -30.0,
+            attack_ms: 10,
+            release_ms: 100,
+            hold_time_ms: 0,
+            min_open_ms: 0,
+            smoothing_window: 3,
+            voice_filter: false,
+            noise_suppression: false,
+            loudness_thresholds_db: vec![],
+            loudness_thresholds: None,
+            input_device: None,
+            audio_host: None,
+            record_to_wav: false,
+            recordings_path: None,
+            monitor_enabled: false,
+            monitor_device: None,
+            input_format: None,
+            channel_mode: ChannelMode::default(),
             flicker_input: false,
+            flicker_min_ms: 30,
+            flicker_max_ms: 100,
+            input_gain: 1.0,
+            chroma_key: false,
+            chroma_key_color: [1.0, 0.0, 1.0],
+            chroma_matte: false,
+            chroma_matte_threshold: 0.5,
+            show_buttons: true,
+            transparent_background: false,
+            window_width: 400.0,
+            window_height: 500.0,
+            window_position: None,
+            window_monitor: None,
+            always_on_top: false,
+            mini_mode: false,
+            mini_size: 160.0,
+            keybinds: Keybinds::default(),
+            confirm_quit: false,
+            theme: "Tokyo Night".to_string(),
+            renderer: RendererBackend::default(),
+            minimize_to_tray: false,
+            avatar_scale: 0.8,
+            mirror_horizontal: false,
+            avatar_offset: [0.0, 0.0],
+            breathing_enabled: false,
+            breathing_period_ms: 3000,
+            breathing_amplitude: 0.03,
+            bounce_intensity: 0.0,
+            assets_path: PathBuf::from("assets"),
+            blink_enabled: false,
+            blink_interval_min_ms: 2000,
+            blink_interval_max_ms: 6000,
+            splash_image: None,
+            closed_image_index: 0,
+            open_image_index: 1,
+            freeze_image: 0,
+            frame_duration_ms: 100,
+            crossfade_ms: 0,
+            state_dirs: HashMap::new(),
+            osc_enabled: false,
+            osc_address: "127.0.0.1:9000".to_string(),
+            websocket_enabled: false,
+            websocket_port: 8765,
+            websocket_bind_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            metrics_enabled: false,
+            metrics_port: 9898,
+            metrics_bind_address: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            ndi_enabled: false,
+            ndi_name: "chibi".to_string(),
+            ndi_fps: 30,
+            total_speaking_ms: 0,
+            total_speaking_secs: None,
+            profiles: BTreeMap::new(),
+            active_profile: None,
         }
     }
 }
 
+/// Move a `config.toml` from the current directory, where older versions kept it,
+/// to `path` if there isn't a config there yet
+fn migrate_legacy_config(path: &Path) -> io::Result<()> {
+    let legacy_path = Path::new(CONFIG_FILE);
+    if path == legacy_path || path.exists() || !legacy_path.is_file() {
+        return Ok(());
+    }
+
+    info!("Moving {} to {}", legacy_path.display(), path.display());
+
+    // Renaming fails across filesystems, so fall back to copying
+    if fs::rename(legacy_path, path).is_err() {
+        fs::copy(legacy_path, path)?;
+        fs::remove_file(legacy_path)?;
+    }
+
+    Ok(())
+}
+
+/// Clamp a value into `range`, logging if it was out of range
+fn clamp_field(name: &str, value: &mut f32, range: RangeInclusive<f32>) {
+    if range.contains(value) {
+        return;
+    }
+
+    let clamped = if value.is_nan() {
+        *range.start()
+    } else {
+        value.clamp(*range.start(), *range.end())
+    };
+
+    warn!(
+        "{} = {} is outside of {:?}, using {} instead",
+        name, value, range, clamped
+    );
+    *value = clamped;
+}
+
+/// Clamp a whole number into `range`, logging if it was out of range
+fn clamp_int_field<T>(name: &str, value: &mut T, range: RangeInclusive<T>)
+where
+    T: Ord + Copy + fmt::Display + fmt::Debug,
+{
+    if range.contains(value) {
+        return;
+    }
+
+    let clamped = (*value).clamp(*range.start(), *range.end());
+    warn!(
+        "{} = {} is outside of {:?}, using {} instead",
+        name, value, range, clamped
+    );
+    *value = clamped;
+}
+
 // Custom serializer to get serde to do what I want
 fn round_to_hundredths<S>(x: &f32, s: S) -> Result<S::Ok, S::Error>
 where
@@ -83,3 +910,101 @@ macro_rules! lock_and_unlock {
         $mutex.lock().unwrap()
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load_str(config_file: &str) -> ChibiConfig {
+        let mut config: ChibiConfig = toml::from_str(config_file).unwrap();
+        config.migrate();
+        config.validate();
+        config
+    }
+
+    #[test]
+    fn migrates_unversioned_config() {
+        let config = load_str(
+            "microphone_threshold = 0.1\n\
+            total_speaking_secs = 5\n\
+            loudness_thresholds = [0.5, 0.1]\n",
+        );
+
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.microphone_threshold_db, -20.0);
+        assert_eq!(config.total_speaking_ms, 5000);
+        assert_eq!(config.loudness_thresholds_db, [-20.0, -6.0]);
+    }
+
+    #[test]
+    fn migration_skips_steps_already_applied() {
+        let config = load_str(
+            "version = 2\n\
+            microphone_threshold_db = -24.0\n\
+            total_speaking_ms = 1500\n\
+            loudness_thresholds = [0.5]\n",
+        );
+
+        assert_eq!(config.version, CONFIG_VERSION);
+        assert_eq!(config.microphone_threshold_db, -24.0);
+        assert_eq!(config.total_speaking_ms, 1500);
+        assert_eq!(config.loudness_thresholds_db, [-6.0]);
+    }
+
+    #[test]
+    fn newer_config_is_left_alone() {
+        let config = load_str(&format!(
+            "version = {}\nloudness_thresholds = [0.5]\n",
+            CONFIG_VERSION + 1
+        ));
+
+        assert_eq!(config.version, CONFIG_VERSION + 1);
+        assert!(config.loudness_thresholds_db.is_empty());
+    }
+
+    #[test]
+    fn migrated_config_doesnt_write_legacy_fields() {
+        let config = load_str("microphone_threshold = 0.1\n");
+        let saved = toml::to_string(&config).unwrap();
+
+        assert!(saved.contains("microphone_threshold_db"));
+        assert!(!saved.contains("microphone_threshold ="));
+    }
+
+    #[test]
+    fn validate_clamps_out_of_range_values() {
+        let config = load_str(
+            "microphone_threshold_db = 6.0\n\
+            input_gain = 0.0\n\
+            crossfade_ms = 5000\n\
+            smoothing_window = 0\n",
+        );
+
+        assert_eq!(config.microphone_threshold_db, 0.0);
+        assert_eq!(config.input_gain, 1.0);
+        assert_eq!(config.crossfade_ms, 2000);
+        assert_eq!(config.smoothing_window, 1);
+    }
+
+    #[test]
+    fn validate_replaces_nan() {
+        let config = load_str("deadband_factor = nan\n");
+
+        assert_eq!(config.deadband_factor, 0.0);
+    }
+
+    #[test]
+    fn validate_orders_loudness_thresholds() {
+        let config = load_str("loudness_thresholds_db = [-6.0, -80.0, -12.0]\n");
+
+        assert_eq!(config.loudness_thresholds_db, [-60.0, -12.0, -6.0]);
+    }
+
+    #[test]
+    fn validate_raises_flicker_max_to_min() {
+        let config = load_str("flicker_min_ms = 200\nflicker_max_ms = 50\n");
+
+        assert_eq!(config.flicker_min_ms, 200);
+        assert_eq!(config.flicker_max_ms, 200);
+    }
+}
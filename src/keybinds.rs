@@ -0,0 +1,162 @@
+use iced::keyboard::Key;
+use iced::keyboard::key::Named;
+use serde::{Deserialize, Serialize};
+
+/// Actions that can be bound to a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyAction {
+    ToggleUi,
+    ToggleChromaKey,
+    Mute,
+    ToggleFullscreen,
+    NextProfile,
+    ToggleMiniMode,
+    Freeze,
+    ToggleDebugOverlay,
+}
+
+/// Keys bound to each action, either a single character (e.g. "c") or the name of a
+/// special key (e.g. "Escape", "Tab", "F1")
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Keybinds {
+    pub toggle_ui: String,
+    pub toggle_chroma_key: String,
+    pub mute: String,
+    pub toggle_fullscreen: String,
+    pub next_profile: String,
+    pub toggle_mini_mode: String,
+    pub freeze: String,
+    pub toggle_debug_overlay: String,
+}
+
+impl Keybinds {
+    /// The action bound to a pressed key, if any
+    pub fn action_for(&self, key: &Key) -> Option<KeyAction> {
+        self.bindings()
+            .into_iter()
+            .find(|(_, binding)| parse_key(binding).is_some_and(|bound| keys_match(&bound, key)))
+            .map(|(action, _)| action)
+    }
+
+    fn bindings(&self) -> [(KeyAction, &str); 8] {
+        [
+            (KeyAction::ToggleUi, &self.toggle_ui),
+            (KeyAction::ToggleChromaKey, &self.toggle_chroma_key),
+            (KeyAction::Mute, &self.mute),
+            (KeyAction::ToggleFullscreen, &self.toggle_fullscreen),
+            (KeyAction::NextProfile, &self.next_profile),
+            (KeyAction::ToggleMiniMode, &self.toggle_mini_mode),
+            (KeyAction::Freeze, &self.freeze),
+            (KeyAction::ToggleDebugOverlay, &self.toggle_debug_overlay),
+        ]
+    }
+}
+
+impl Default for Keybinds {
+    fn default() -> Self {
+        Self {
+            toggle_ui: "Escape".to_string(),
+            toggle_chroma_key: "c".to_string(),
+            mute: "m".to_string(),
+            toggle_fullscreen: "F11".to_string(),
+            next_profile: "Tab".to_string(),
+            toggle_mini_mode: "F10".to_string(),
+            freeze: "b".to_string(),
+            toggle_debug_overlay: "F3".to_string(),
+        }
+    }
+}
+
+/// Parse a key binding into an iced key
+pub fn parse_key(binding: &str) -> Option<Key> {
+    let binding = binding.trim();
+
+    let named = match binding.to_lowercase().as_str() {
+        "escape" | "esc" => Named::Escape,
+        "tab" => Named::Tab,
+        "space" => Named::Space,
+        "enter" | "return" => Named::Enter,
+        "backspace" => Named::Backspace,
+        "delete" => Named::Delete,
+        "insert" => Named::Insert,
+        "home" => Named::Home,
+        "end" => Named::End,
+        "pageup" => Named::PageUp,
+        "pagedown" => Named::PageDown,
+        "f1" => Named::F1,
+        "f2" => Named::F2,
+        "f3" => Named::F3,
+        "f4" => Named::F4,
+        "f5" => Named::F5,
+        "f6" => Named::F6,
+        "f7" => Named::F7,
+        "f8" => Named::F8,
+        "f9" => Named::F9,
+        "f10" => Named::F10,
+        "f11" => Named::F11,
+        "f12" => Named::F12,
+        _ if binding.chars().count() == 1 => return Some(Key::Character(binding.into())),
+        _ => return None,
+    };
+
+    Some(Key::Named(named))
+}
+
+// Characters are compared case-insensitively, so bindings still work with shift held
+fn keys_match(bound: &Key, pressed: &Key) -> bool {
+    match (bound, pressed) {
+        (Key::Character(bound), Key::Character(pressed)) => {
+            bound.to_lowercase() == pressed.to_lowercase()
+        }
+        _ => bound == pressed,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_named_keys_in_any_case() {
+        assert_eq!(parse_key("Escape"), Some(Key::Named(Named::Escape)));
+        assert_eq!(parse_key("esc"), Some(Key::Named(Named::Escape)));
+        assert_eq!(parse_key(" PageUp "), Some(Key::Named(Named::PageUp)));
+        assert_eq!(parse_key("f11"), Some(Key::Named(Named::F11)));
+    }
+
+    #[test]
+    fn parses_single_characters() {
+        assert_eq!(parse_key("c"), Some(Key::Character("c".into())));
+        assert_eq!(parse_key("ä"), Some(Key::Character("ä".into())));
+    }
+
+    #[test]
+    fn rejects_unknown_bindings() {
+        assert_eq!(parse_key(""), None);
+        assert_eq!(parse_key("ctrl+c"), None);
+        assert_eq!(parse_key("F13"), None);
+    }
+
+    #[test]
+    fn default_bindings_all_parse() {
+        for (action, binding) in Keybinds::default().bindings() {
+            assert!(parse_key(binding).is_some(), "{:?} = {}", action, binding);
+        }
+    }
+
+    #[test]
+    fn characters_match_with_shift_held() {
+        let keybinds = Keybinds::default();
+
+        assert_eq!(
+            keybinds.action_for(&Key::Character("C".into())),
+            Some(KeyAction::ToggleChromaKey)
+        );
+        assert_eq!(
+            keybinds.action_for(&Key::Named(Named::F3)),
+            Some(KeyAction::ToggleDebugOverlay)
+        );
+        assert_eq!(keybinds.action_for(&Key::Character("x".into())), None);
+    }
+}
@@ -1,3 +1,12 @@
 pub mod app;
 pub mod capture;
 pub mod config;
+pub mod keybinds;
+mod listener;
+pub mod metrics;
+pub mod monitors;
+#[cfg(feature = "ndi")]
+pub mod ndi;
+#[cfg(feature = "tray")]
+pub mod tray;
+pub mod websocket;
@@ -0,0 +1,76 @@
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Thread accepting connections on a listener until stopped
+///
+/// Accepting blocks, so stopping connects to the listener once to wake the thread up.
+/// The thread is stopped and joined when this is dropped
+pub struct AcceptLoop {
+    address: SocketAddr,
+    stopped: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl AcceptLoop {
+    /// Hand every accepted connection to `handle_stream` on a new thread
+    pub fn spawn(
+        listener: TcpListener,
+        mut handle_stream: impl FnMut(TcpStream) + Send + 'static,
+    ) -> io::Result<Self> {
+        let address = listener.local_addr()?;
+        let stopped = Arc::new(AtomicBool::new(false));
+
+        let accept_stopped = stopped.clone();
+        let handle = std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                if accept_stopped.load(Ordering::Relaxed) {
+                    break;
+                }
+
+                if let Ok(stream) = stream {
+                    handle_stream(stream);
+                }
+            }
+        });
+
+        Ok(Self {
+            address,
+            stopped,
+            handle: Some(handle),
+        })
+    }
+
+    /// Stop accepting connections and close the listener
+    pub fn stop(&mut self) {
+        let Some(handle) = self.handle.take() else {
+            return;
+        };
+
+        self.stopped.store(true, Ordering::Relaxed);
+
+        // A listener on every interface can be reached over loopback
+        let mut address = self.address;
+        if address.ip().is_unspecified() {
+            address.set_ip(match address.ip() {
+                IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::LOCALHOST),
+                IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::LOCALHOST),
+            });
+        }
+
+        // If the listener can't be reached the thread is left behind rather than
+        // blocking forever
+        if TcpStream::connect_timeout(&address, Duration::from_secs(1)).is_ok() {
+            handle.join().ok();
+        }
+    }
+}
+
+impl Drop for AcceptLoop {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -3,46 +3,183 @@
 // Licensed under the MPL-2.0 license
 //
 
-use app::{ChibiApp, Message};
+use app::{ChibiApp, Message, SetupStep, View};
+use capture::MicEvent;
+use chibi::metrics::MetricsServer;
+use chibi::websocket::WebSocketServer;
 use chibi::{app, capture, config, lock_and_unlock};
 use config::ChibiConfig;
 
-use iced::{Task, Theme};
-use std::sync::{Arc, Mutex};
+use clap::Parser;
+use iced::window::Position;
+use iced::{Point, Size, Task};
+use log::{error, warn};
+use std::path::PathBuf;
+
+/// Indie PNG-tuber application made in Rust supporting all major platforms
+#[derive(Parser)]
+#[command(version, about)]
+struct Args {
+    /// Config file to use instead of the one in the platform's config directory
+    #[arg(long, value_name = "PATH")]
+    config: Option<PathBuf>,
+
+    /// Input device to use, by the name shown in the settings
+    #[arg(long, value_name = "NAME")]
+    device: Option<String>,
+
+    /// Folder containing the avatar images
+    #[arg(long, value_name = "DIR")]
+    assets: Option<PathBuf>,
+
+    /// Drive the avatar from a generated test signal instead of a microphone
+    #[arg(long)]
+    test_signal: bool,
+}
 
 fn main() -> iced::Result {
-    // Create a channel to communicate with the detector thread
-    let (sender, receiever) = async_channel::unbounded();
-    let mut app = ChibiApp::new(ChibiConfig::default(), Some(receiever.clone()));
+    let args = Args::parse();
+
+    // Only warnings and errors by default, more with e.g. RUST_LOG=chibi=debug
+    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("warn")).init();
+
+    if let Some(config_path) = args.config {
+        ChibiConfig::set_path(config_path);
+    }
+
+    // Create a channel to receive detection events (activity and RMS level) from the
+    // detector thread
+    let (sender, receiver) = async_channel::unbounded::<MicEvent>();
+    let mut app = ChibiApp::new(ChibiConfig::default(), Some(receiver.clone()));
+
+    // Messages for the notification banner, from startup and the capture thread
+    let (notifier, notifications) = async_channel::unbounded::<String>();
+    app.notifier = Some(notifier.clone());
+
+    // Walk new users through picking their avatar and microphone
+    if !ChibiConfig::exists() {
+        app.curr_view = View::Setup(SetupStep::Assets);
+    }
+
+    if let Err(err) = lock_and_unlock!(app.config).load() {
+        let message = format!(
+            "Failed to load {}, using defaults: {}",
+            ChibiConfig::path().display(),
+            err
+        );
+
+        warn!("{}", message);
+        notifier.try_send(message).ok();
+    }
+
+    // Command line options take precedence over the config file
+    {
+        let mut config = lock_and_unlock!(app.config);
+
+        if let Some(device) = args.device {
+            config.input_device = Some(device);
+        }
+
+        if let Some(assets) = args.assets {
+            config.assets_path = assets;
+        }
+    }
+
+    // iced only picks the renderer from the environment. This runs before any other
+    // threads are started, so nothing else can be reading the environment
+    let renderer = lock_and_unlock!(app.config).renderer;
+    if let Some(backend) = renderer.iced_backend()
+        && std::env::var_os("ICED_BACKEND").is_none()
+    {
+        unsafe { std::env::set_var("ICED_BACKEND", backend) };
+    }
+
+    // Load images from the configured assets folder
+    let assets_path = lock_and_unlock!(app.config).assets_path.clone();
+    app.load_images(&assets_path);
+
+    app.load_input_devices();
+    app.restore_input_device();
+
+    let config = lock_and_unlock!(app.config).clone();
+
+    if config.websocket_enabled {
+        app.websocket =
+            WebSocketServer::start(config.websocket_bind_address, config.websocket_port)
+                .inspect_err(|err| error!("Failed to start the WebSocket server: {}", err))
+                .ok();
+    }
+
+    if config.metrics_enabled {
+        app.metrics = MetricsServer::start(config.metrics_bind_address, config.metrics_port)
+            .inspect_err(|err| error!("Failed to start the metrics server: {}", err))
+            .ok();
+    }
+
+    #[cfg(feature = "ndi")]
+    if config.ndi_enabled {
+        app.ndi = chibi::ndi::NdiOutput::start(&config.ndi_name, config.ndi_fps.clamp(1, 60))
+            .inspect_err(|err| error!("Failed to start the NDI output: {}", err))
+            .ok();
+    }
+
+    #[cfg(feature = "tray")]
+    {
+        app.tray = chibi::tray::Tray::new();
+    }
+
+    app.test_signal = args.test_signal;
+
+    // Spawn the capture thread, which stops when the app drops the session
+    app.sender = Some(sender);
+    app.restart_capture();
 
-    // Load images from assets in the current directory
-    let current_dir = std::env::current_dir().expect("Failed to get current directory");
-    let assets_dir = current_dir.join("assets");
+    let config = lock_and_unlock!(app.config).clone();
+    let window_level = app::window_level(config.always_on_top);
+    let window_size = if config.mini_mode {
+        Size::new(config.mini_size, config.mini_size)
+    } else {
+        Size::new(config.window_width, config.window_height)
+    };
 
-    lock_and_unlock!(app.config).load();
-    app.load_images(&assets_dir);
+    // Open on the chosen monitor, at the saved position if that is on it
+    let monitor = config.window_monitor.and_then(|index| {
+        chibi::monitors::get_monitors()
+            .into_iter()
+            .find(|monitor| monitor.index == index)
+    });
+    let saved_position = config.window_position.map(|[x, y]| Point::new(x, y));
 
-    let input_device = Arc::new(Mutex::new(app.selected_input_device.clone().unwrap()));
-    let input_config = Arc::new(Mutex::new(app.selected_input_config.clone()));
+    let window_position = match (monitor, saved_position) {
+        (Some(monitor), position) if !position.is_some_and(|p| monitor.contains(p)) => {
+            Position::Specific(monitor.centered(window_size))
+        }
+        (_, Some(position)) => Position::Specific(position),
+        _ => Position::default(),
+    };
 
-    // Spawn the capture thread
-    capture::spawn_capture_thread(
-        app.config.clone(),
-        Arc::new(Mutex::new(input_device.lock().unwrap().raw_device.clone())),
-        input_config,
-        sender,
-    );
+    // Transparency can only be set when the window is created
+    app.transparent_window = config.transparent_background;
 
-    // Capture the stream of messages from the capture thread and turn them into messages
-    let stream_task = Task::stream(receiever).map(Message::MicActive);
+    // Turn the detection events and notifications into messages
+    let stream_task = Task::batch([
+        Task::stream(receiver).map(Message::MicActive),
+        Task::stream(notifications).map(Message::ShowNotification),
+    ]);
 
     iced::application("chibi", ChibiApp::update, ChibiApp::view)
-        .theme(move |_| Theme::TokyoNight)
+        .theme(ChibiApp::theme)
+        .style(ChibiApp::style)
         .window(iced::window::Settings {
-            size: (400.0, 500.0).into(),
-            resizable: false,
+            size: window_size,
+            position: window_position,
+            resizable: true,
+            level: window_level,
+            transparent: app.transparent_window,
+            decorations: !config.mini_mode,
             ..Default::default()
         })
+        .exit_on_close_request(false)
         .subscription(ChibiApp::subscription)
         .run_with(|| (app, stream_task))
 }
@@ -0,0 +1,137 @@
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{IpAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::{info, warn};
+
+use crate::capture::MicEvent;
+use crate::listener::AcceptLoop;
+
+/// Latest detection state, as served to scrapers
+struct Metrics {
+    active: bool,
+    raw_rms: f32,
+    level: f32,
+    activations: u64,
+    started: Instant,
+}
+
+/// HTTP server exposing the detection state in the Prometheus text format, for
+/// monitoring chibi from a streaming rig
+///
+/// Any request is answered with the metrics, so it can be scraped from any path. The
+/// server stops listening when this is dropped
+pub struct MetricsServer {
+    metrics: Arc<Mutex<Metrics>>,
+    _accept_loop: AcceptLoop,
+}
+
+impl MetricsServer {
+    /// Start listening for scrapes on `port` at `address`
+    pub fn start(address: IpAddr, port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind((address, port))?;
+        let metrics = Arc::new(Mutex::new(Metrics {
+            active: false,
+            raw_rms: 0.0,
+            level: 0.0,
+            activations: 0,
+            started: Instant::now(),
+        }));
+
+        let served_metrics = metrics.clone();
+        let accept_loop = AcceptLoop::spawn(listener, move |stream| {
+            if let Err(err) = serve_scrape(stream, &served_metrics) {
+                warn!("Failed to serve metrics: {}", err);
+            }
+        })?;
+
+        info!("Metrics server listening on {}:{}", address, port);
+        Ok(Self {
+            metrics,
+            _accept_loop: accept_loop,
+        })
+    }
+
+    /// Record a detection event, to be served on the next scrape
+    pub fn update(&self, event: &MicEvent) {
+        let mut metrics = self.metrics.lock().unwrap();
+
+        if event.active && !metrics.active {
+            metrics.activations += 1;
+        }
+
+        metrics.active = event.active;
+        metrics.raw_rms = event.raw_rms;
+        metrics.level = event.rms;
+    }
+}
+
+fn serve_scrape(stream: TcpStream, metrics: &Mutex<Metrics>) -> io::Result<()> {
+    // Scrapes are small and infrequent, so they are answered one at a time. Only the
+    // headers are read, the request itself doesn't matter
+    stream.set_read_timeout(Some(Duration::from_secs(1)))?;
+    let mut reader = BufReader::new(&stream);
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 && !line.trim_end().is_empty() {
+        line.clear();
+    }
+
+    let body = render(&metrics.lock().unwrap());
+    let mut stream = &stream;
+    write!(
+        stream,
+        "HTTP/1.1 200 OK\r\n\
+        Content-Type: text/plain; version=0.0.4\r\n\
+        Content-Length: {}\r\n\
+        Connection: close\r\n\r\n{}",
+        body.len(),
+        body
+    )
+}
+
+fn render(metrics: &Metrics) -> String {
+    let series = [
+        (
+            "chibi_mic_active",
+            "gauge",
+            "Whether the microphone is considered active",
+            if metrics.active { 1.0 } else { 0.0 },
+        ),
+        (
+            "chibi_raw_rms",
+            "gauge",
+            "RMS amplitude of the input as received from the device",
+            metrics.raw_rms as f64,
+        ),
+        (
+            "chibi_level",
+            "gauge",
+            "Level of the input after processing as compared against the threshold, \
+            the RMS or peak amplitude depending on the detection mode",
+            metrics.level as f64,
+        ),
+        (
+            "chibi_activations_total",
+            "counter",
+            "Number of times the microphone became active",
+            metrics.activations as f64,
+        ),
+        (
+            "chibi_uptime_seconds",
+            "counter",
+            "Time since chibi was started",
+            metrics.started.elapsed().as_secs_f64(),
+        ),
+    ];
+
+    series
+        .iter()
+        .map(|(name, kind, help, value)| {
+            format!(
+                "# HELP {} {}\n# TYPE {} {}\n{} {}\n",
+                name, help, name, kind, name, value
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,82 @@
+use std::fmt;
+
+use display_info::DisplayInfo;
+use iced::{Point, Size};
+use log::warn;
+
+/// A connected monitor the window can be opened on
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonitorInfo {
+    /// Position in the list of monitors, which is what the config refers to them by
+    pub index: usize,
+
+    /// Position and size in logical pixels
+    pub position: Point,
+    pub size: Size,
+
+    pub primary: bool,
+}
+
+impl MonitorInfo {
+    /// Whether a point in logical pixels lies on the monitor
+    pub fn contains(&self, point: Point) -> bool {
+        point.x >= self.position.x
+            && point.y >= self.position.y
+            && point.x < self.position.x + self.size.width
+            && point.y < self.position.y + self.size.height
+    }
+
+    /// Position that centers a window of the given size on the monitor
+    pub fn centered(&self, window_size: Size) -> Point {
+        Point::new(
+            self.position.x + ((self.size.width - window_size.width) / 2.0).max(0.0),
+            self.position.y + ((self.size.height - window_size.height) / 2.0).max(0.0),
+        )
+    }
+}
+
+impl fmt::Display for MonitorInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Monitor {} ({}x{}{})",
+            self.index + 1,
+            self.size.width,
+            self.size.height,
+            if self.primary { ", primary" } else { "" }
+        )
+    }
+}
+
+/// List the connected monitors, in the order the platform reports them
+pub fn get_monitors() -> Vec<MonitorInfo> {
+    let displays = match DisplayInfo::all() {
+        Ok(displays) => displays,
+        Err(err) => {
+            warn!("Failed to list monitors: {}", err);
+            return vec![];
+        }
+    };
+
+    displays
+        .into_iter()
+        .enumerate()
+        .map(|(index, display)| {
+            // Windows are positioned in logical pixels
+            let scale_factor = display.scale_factor.max(0.1);
+
+            MonitorInfo {
+                index,
+                position: Point::new(
+                    display.x as f32 / scale_factor,
+                    display.y as f32 / scale_factor,
+                ),
+                size: Size::new(
+                    (display.width as f32 / scale_factor).round(),
+                    (display.height as f32 / scale_factor).round(),
+                ),
+                primary: display.is_primary,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,220 @@
+use std::ffi::{CString, c_char, c_float, c_int, c_void};
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+
+use iced::window::Screenshot;
+use libloading::Library;
+use log::{debug, info};
+
+// Names the NDI runtime is installed under, tried in order
+#[cfg(target_os = "windows")]
+const LIBRARY_NAMES: &[&str] = &["Processing.NDI.Lib.x64.dll"];
+#[cfg(target_os = "macos")]
+const LIBRARY_NAMES: &[&str] = &["libndi.dylib", "/usr/local/lib/libndi.dylib"];
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+const LIBRARY_NAMES: &[&str] = &["libndi.so.6", "libndi.so.5", "libndi.so"];
+
+// Set by the NDI runtime installers to the folder the runtime was installed to
+const RUNTIME_DIR_VARS: &[&str] = &["NDI_RUNTIME_DIR_V6", "NDI_RUNTIME_DIR_V5"];
+
+// Values from the NDI SDK headers
+const FOURCC_RGBA: u32 = u32::from_le_bytes(*b"RGBA");
+const FRAME_FORMAT_PROGRESSIVE: c_int = 1;
+const TIMECODE_SYNTHESIZE: i64 = i64::MAX;
+
+/// `NDIlib_send_create_t`
+#[repr(C)]
+struct SendCreateSettings {
+    ndi_name: *const c_char,
+    groups: *const c_char,
+    clock_video: bool,
+    clock_audio: bool,
+}
+
+/// `NDIlib_video_frame_v2_t`
+#[repr(C)]
+struct VideoFrame {
+    xres: c_int,
+    yres: c_int,
+    fourcc: u32,
+    frame_rate_n: c_int,
+    frame_rate_d: c_int,
+    picture_aspect_ratio: c_float,
+    frame_format_type: c_int,
+    timecode: i64,
+    data: *const u8,
+    line_stride_in_bytes: c_int,
+    metadata: *const c_char,
+    timestamp: i64,
+}
+
+type InitializeFn = unsafe extern "C" fn() -> bool;
+type DestroyFn = unsafe extern "C" fn();
+type SendCreateFn = unsafe extern "C" fn(*const SendCreateSettings) -> *mut c_void;
+type SendDestroyFn = unsafe extern "C" fn(*mut c_void);
+type SendVideoFn = unsafe extern "C" fn(*mut c_void, *const VideoFrame);
+
+/// An NDI sender created from the runtime library, which is kept loaded while it exists
+struct Sender {
+    library: Library,
+    instance: *mut c_void,
+}
+
+// SAFETY: NDI send instances may be used from any thread, as long as they aren't used
+// from several at once. The sender is only ever used from the thread it is moved to
+unsafe impl Send for Sender {}
+
+impl Sender {
+    fn create(name: &str) -> Result<Self, String> {
+        let library = load_runtime()?;
+        let name = CString::new(name).map_err(|err| err.to_string())?;
+
+        // SAFETY: the signatures match the NDI SDK headers, and the settings outlive the call
+        let instance = unsafe {
+            let initialize = library
+                .get::<InitializeFn>(b"NDIlib_initialize\0")
+                .map_err(|err| err.to_string())?;
+            if !initialize() {
+                return Err("the NDI runtime isn't supported on this CPU".to_string());
+            }
+
+            let send_create = library
+                .get::<SendCreateFn>(b"NDIlib_send_create\0")
+                .map_err(|err| err.to_string())?;
+
+            // Frames are sent as they are captured, the capture timer paces them
+            send_create(&SendCreateSettings {
+                ndi_name: name.as_ptr(),
+                groups: std::ptr::null(),
+                clock_video: false,
+                clock_audio: false,
+            })
+        };
+
+        if instance.is_null() {
+            return Err("failed to create the NDI sender".to_string());
+        }
+
+        Ok(Self { library, instance })
+    }
+
+    fn send(&self, frame: &Screenshot, fps: u32) {
+        let (width, height) = (frame.size.width, frame.size.height);
+        if width == 0 || height == 0 || frame.bytes.len() < width as usize * height as usize * 4 {
+            return;
+        }
+
+        let video_frame = VideoFrame {
+            xres: width as c_int,
+            yres: height as c_int,
+            fourcc: FOURCC_RGBA,
+            frame_rate_n: fps as c_int,
+            frame_rate_d: 1,
+            picture_aspect_ratio: width as f32 / height as f32,
+            frame_format_type: FRAME_FORMAT_PROGRESSIVE,
+            timecode: TIMECODE_SYNTHESIZE,
+            data: frame.bytes.as_ptr(),
+            line_stride_in_bytes: width as c_int * 4,
+            metadata: std::ptr::null(),
+            timestamp: 0,
+        };
+
+        // SAFETY: the frame and its pixels outlive the call, which copies what it needs
+        unsafe {
+            if let Ok(send_video) = self
+                .library
+                .get::<SendVideoFn>(b"NDIlib_send_send_video_v2\0")
+            {
+                send_video(self.instance, &video_frame);
+            }
+        }
+    }
+}
+
+impl Drop for Sender {
+    fn drop(&mut self) {
+        // SAFETY: the instance was created by this library and isn't used after this
+        unsafe {
+            if let Ok(send_destroy) = self.library.get::<SendDestroyFn>(b"NDIlib_send_destroy\0") {
+                send_destroy(self.instance);
+            }
+
+            if let Ok(destroy) = self.library.get::<DestroyFn>(b"NDIlib_destroy\0") {
+                destroy();
+            }
+        }
+    }
+}
+
+/// Publishes the rendered window as an NDI source, so OBS and other NDI receivers can take
+/// the avatar in directly, with transparency when the window background is transparent
+///
+/// The NDI runtime is loaded when the output starts, so chibi still runs on machines
+/// without it. Frames are sent from a thread of their own, and frames that arrive while
+/// the previous one is still being sent are dropped
+pub struct NdiOutput {
+    frames: Option<mpsc::SyncSender<Screenshot>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl NdiOutput {
+    /// Start publishing a source with the given name at up to `fps` frames per second
+    pub fn start(name: &str, fps: u32) -> Result<Self, String> {
+        let sender = Sender::create(name)?;
+        let (frames, receiver) = mpsc::sync_channel::<Screenshot>(1);
+
+        let handle = std::thread::spawn(move || {
+            for frame in receiver {
+                sender.send(&frame, fps);
+            }
+        });
+
+        info!("Publishing NDI source '{}'", name);
+        Ok(Self {
+            frames: Some(frames),
+            handle: Some(handle),
+        })
+    }
+
+    /// Queue a captured frame of the window for sending
+    pub fn push(&self, frame: Screenshot) {
+        if let Some(frames) = &self.frames {
+            frames.try_send(frame).ok();
+        }
+    }
+}
+
+impl Drop for NdiOutput {
+    fn drop(&mut self) {
+        // Closing the channel ends the thread, which then destroys the sender
+        self.frames.take();
+
+        if let Some(handle) = self.handle.take() {
+            handle.join().ok();
+        }
+    }
+}
+
+/// Load the NDI runtime from the folder its installer points to, falling back to the
+/// system's library search path
+fn load_runtime() -> Result<Library, String> {
+    let runtime_dirs = RUNTIME_DIR_VARS
+        .iter()
+        .filter_map(std::env::var_os)
+        .map(PathBuf::from);
+
+    let candidates = runtime_dirs
+        .flat_map(|dir| LIBRARY_NAMES.iter().map(move |name| dir.join(name)))
+        .chain(LIBRARY_NAMES.iter().map(PathBuf::from));
+
+    for candidate in candidates {
+        // SAFETY: loading the NDI runtime runs no initialisation beyond the library's own
+        match unsafe { Library::new(&candidate) } {
+            Ok(library) => return Ok(library),
+            Err(err) => debug!("Couldn't load {}: {}", candidate.display(), err),
+        }
+    }
+
+    Err("the NDI runtime isn't installed, get it from https://ndi.video/tools/".to_string())
+}
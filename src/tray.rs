@@ -0,0 +1,125 @@
+use log::warn;
+use tray_icon::menu::{Menu, MenuEvent, MenuItem, PredefinedMenuItem};
+use tray_icon::{Icon, TrayIcon, TrayIconBuilder};
+
+// The example avatar doubles as the tray icon
+const ICON: &[u8] = include_bytes!("../assets/1.png");
+const ICON_SIZE: u32 = 64;
+
+// Ids of the tray menu items
+const SHOW_ID: &str = "show";
+const MUTE_ID: &str = "mute";
+const CHROMA_KEY_ID: &str = "chroma_key";
+const QUIT_ID: &str = "quit";
+
+/// Actions that can be picked from the tray menu
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrayAction {
+    Show,
+    Mute,
+    ToggleChromaKey,
+    Quit,
+}
+
+/// System tray icon with a menu for quick access to common actions
+///
+/// On Linux the tray icon lives on its own thread running the GTK event loop, elsewhere it
+/// lives on the main thread and is removed when this is dropped
+pub struct Tray {
+    _icon: Option<TrayIcon>,
+}
+
+impl Tray {
+    pub fn new() -> Option<Self> {
+        #[cfg(target_os = "linux")]
+        {
+            // The icon is created on the GTK thread, which reports back whether that worked
+            // so a missing icon isn't mistaken for a working one
+            let (created_sender, created_receiver) = std::sync::mpsc::channel();
+
+            std::thread::spawn(move || {
+                if let Err(err) = gtk::init() {
+                    warn!("Failed to initialize GTK for the tray icon: {}", err);
+                    created_sender.send(false).ok();
+                    return;
+                }
+
+                let Some(_icon) = build_tray_icon() else {
+                    created_sender.send(false).ok();
+                    return;
+                };
+
+                created_sender.send(true).ok();
+                gtk::main();
+            });
+
+            created_receiver
+                .recv()
+                .unwrap_or(false)
+                .then_some(Self { _icon: None })
+        }
+
+        #[cfg(not(target_os = "linux"))]
+        {
+            build_tray_icon().map(|icon| Self { _icon: Some(icon) })
+        }
+    }
+
+    /// Actions picked from the menu since the last call
+    pub fn poll_actions() -> Vec<TrayAction> {
+        MenuEvent::receiver()
+            .try_iter()
+            .filter_map(|event| match event.id.as_ref() {
+                SHOW_ID => Some(TrayAction::Show),
+                MUTE_ID => Some(TrayAction::Mute),
+                CHROMA_KEY_ID => Some(TrayAction::ToggleChromaKey),
+                QUIT_ID => Some(TrayAction::Quit),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+fn build_tray_icon() -> Option<TrayIcon> {
+    let menu = Menu::new();
+    let items = [
+        &MenuItem::with_id(SHOW_ID, "Show window", true, None),
+        &MenuItem::with_id(MUTE_ID, "Mute", true, None),
+        &MenuItem::with_id(CHROMA_KEY_ID, "Toggle chroma key", true, None),
+    ];
+
+    for item in items {
+        menu.append(item).ok();
+    }
+
+    menu.append(&PredefinedMenuItem::separator()).ok();
+    menu.append(&MenuItem::with_id(QUIT_ID, "Quit", true, None))
+        .ok();
+
+    let result = TrayIconBuilder::new()
+        .with_menu(Box::new(menu))
+        .with_tooltip("chibi")
+        .with_icon(load_icon()?)
+        .build();
+
+    match result {
+        Ok(icon) => Some(icon),
+        Err(err) => {
+            warn!("Failed to create the tray icon: {}", err);
+            None
+        }
+    }
+}
+
+fn load_icon() -> Option<Icon> {
+    let image = match ::image::load_from_memory(ICON) {
+        Ok(image) => image.thumbnail(ICON_SIZE, ICON_SIZE).into_rgba8(),
+        Err(err) => {
+            warn!("Failed to load the tray icon: {}", err);
+            return None;
+        }
+    };
+
+    let (width, height) = image.dimensions();
+    Icon::from_rgba(image.into_raw(), width, height).ok()
+}
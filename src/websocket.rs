@@ -0,0 +1,114 @@
+use std::io;
+use std::net::{IpAddr, TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use log::{info, warn};
+use tungstenite::{Message, WebSocket};
+
+use crate::capture::MicEvent;
+use crate::listener::AcceptLoop;
+
+/// WebSocket server broadcasting the microphone state as JSON, for browser overlays
+///
+// Longest a client gets to complete the handshake or answer the close frame
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// A connected client, fed the JSON to send through its channel. Dropping the sender
+/// closes the connection
+struct Client {
+    sender: mpsc::Sender<String>,
+    handle: JoinHandle<()>,
+}
+
+/// WebSocket server broadcasting the microphone state as JSON, for browser overlays
+///
+/// Every client gets its own thread, so a slow client never holds up the UI. When this
+/// is dropped the server stops listening and closes every connection cleanly
+pub struct WebSocketServer {
+    clients: Arc<Mutex<Vec<Client>>>,
+    accept_loop: AcceptLoop,
+}
+
+impl WebSocketServer {
+    /// Start listening for clients on `port` at `address`
+    pub fn start(address: IpAddr, port: u16) -> io::Result<Self> {
+        let listener = TcpListener::bind((address, port))?;
+        let clients = Arc::new(Mutex::new(Vec::new()));
+
+        let accepted_clients = clients.clone();
+        let accept_loop = AcceptLoop::spawn(listener, move |stream| {
+            let (sender, receiver) = mpsc::channel();
+            let handle = std::thread::spawn(move || serve_client(stream, receiver));
+
+            accepted_clients
+                .lock()
+                .unwrap()
+                .push(Client { sender, handle });
+        })?;
+
+        info!("WebSocket server listening on {}:{}", address, port);
+        Ok(Self {
+            clients,
+            accept_loop,
+        })
+    }
+
+    /// Send a detection event to every connected client
+    pub fn broadcast(&self, event: &MicEvent) {
+        let json = serde_json::json!({
+            "active": event.active,
+            "rms": event.rms,
+            "intensity": event.intensity,
+        })
+        .to_string();
+
+        // Clients that have disconnected have dropped their receiver
+        self.clients
+            .lock()
+            .unwrap()
+            .retain(|client| client.sender.send(json.clone()).is_ok());
+    }
+}
+
+impl Drop for WebSocketServer {
+    fn drop(&mut self) {
+        self.accept_loop.stop();
+
+        // Dropping the senders makes each client thread send a close frame, wait for
+        // the client to answer it, and finish
+        let clients = std::mem::take(&mut *self.clients.lock().unwrap());
+        for Client { sender, handle } in clients {
+            drop(sender);
+            handle.join().ok();
+        }
+    }
+}
+
+fn serve_client(stream: TcpStream, receiver: mpsc::Receiver<String>) {
+    // Bounds the handshake and the wait for the close frame to be answered, nothing else
+    // is read from clients
+    stream.set_read_timeout(Some(CLIENT_TIMEOUT)).ok();
+
+    let mut socket: WebSocket<TcpStream> = match tungstenite::accept(stream) {
+        Ok(socket) => socket,
+        Err(err) => {
+            warn!("WebSocket handshake failed: {}", err);
+            return;
+        }
+    };
+
+    for json in receiver {
+        if socket.send(Message::text(json)).is_err() {
+            return;
+        }
+    }
+
+    // The server is shutting down, so say goodbye rather than just dropping the
+    // connection. Reading drives the close handshake until the client answers
+    if socket.close(None).is_ok() {
+        while socket.read().is_ok() {}
+    }
+}
@@ -0,0 +1,48 @@
+use std::f32::consts::{FRAC_1_SQRT_2, TAU};
+
+use chibi::capture::dsp::{SILENCE_DB, amplitude_to_db, peak_amplitude, rms_amplitude};
+
+#[test]
+fn rms_of_empty_signal_is_silence() {
+    assert_eq!(rms_amplitude(&[]), 0.0);
+    assert_eq!(amplitude_to_db(rms_amplitude(&[])), SILENCE_DB);
+}
+
+#[test]
+fn rms_of_silence_is_zero() {
+    assert_eq!(rms_amplitude(&[0.0; 480]), 0.0);
+}
+
+#[test]
+fn rms_of_full_scale_square_wave_is_one() {
+    let square: Vec<f32> = (0..480)
+        .map(|i| if i % 2 == 0 { 1.0 } else { -1.0 })
+        .collect();
+
+    assert!((rms_amplitude(&square) - 1.0).abs() < 1e-6);
+}
+
+#[test]
+fn rms_of_full_scale_sine_wave() {
+    // Whole number of periods, so the result doesn't depend on where the wave is cut off
+    let sine: Vec<f32> = (0..4800)
+        .map(|i| (TAU * 440.0 * i as f32 / 48_000.0).sin())
+        .collect();
+
+    assert!((rms_amplitude(&sine) - FRAC_1_SQRT_2).abs() < 1e-3);
+}
+
+#[test]
+fn peak_of_empty_signal_is_silence() {
+    assert_eq!(peak_amplitude(&[]), 0.0);
+}
+
+#[test]
+fn peak_is_largest_absolute_sample() {
+    // A single sharp transient barely moves the RMS amplitude, but sets the peak
+    let mut click = vec![0.01; 480];
+    click[100] = -0.9;
+
+    assert_eq!(peak_amplitude(&click), 0.9);
+    assert!(rms_amplitude(&click) < 0.05);
+}